@@ -0,0 +1,68 @@
+//! Compares mosaic composition on a single thread and on all cores
+//!
+//! Run with `cargo bench --no-default-features`
+
+use nanocv::{ImgBuf, ImgSize};
+use nanomosaic::{create_mosaic, Mirror, Kaleidoscope, MosaicPattern, WallpaperGroup};
+use rayon::ThreadPoolBuilder;
+use std::time::{Duration, Instant};
+
+const PATCH_SIZE: usize = 1024;
+const REPEAT: (usize, usize) = (2, 2);
+const RUNS: u32 = 3;
+
+fn main() {
+    let patch = create_patch(ImgSize::new(PATCH_SIZE, PATCH_SIZE));
+    let kaleidoscope = Kaleidoscope::new(8);
+
+    let patterns: &[(&str, &dyn MosaicPattern)] = &[
+        ("mirror", &Mirror),
+        ("p6m", &WallpaperGroup::P6m),
+        ("kaleidoscope", &kaleidoscope),
+    ];
+
+    let serial = ThreadPoolBuilder::new().num_threads(1).build()
+        .expect("Could not create thread pool");
+
+    println!("Patch {0} x {0}, repeat {1} x {2}", PATCH_SIZE, REPEAT.0, REPEAT.1);
+
+    for &(name, pattern) in patterns {
+        let single = serial.install(|| measure(&patch, pattern));
+        let parallel = measure(&patch, pattern);
+
+        println!(
+            "{:<14} 1 thread: {:>8.1} ms, {} threads: {:>8.1} ms, speed-up {:.2}x",
+            name, 
+            millis(single), 
+            rayon::current_num_threads(), 
+            millis(parallel), 
+            single.as_secs_f64()/parallel.as_secs_f64()
+        );
+    }
+}
+
+/// Best time of several runs
+fn measure(patch: &ImgBuf<[u8; 4]>, pattern: &dyn MosaicPattern) -> Duration {
+    (0..RUNS).map(|_| {
+        let start = Instant::now();
+        let mosaic = create_mosaic(patch, pattern, REPEAT);
+        let elapsed = start.elapsed();
+        drop(mosaic);
+        elapsed
+    }).min().unwrap_or_default()
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64()*1000.0
+}
+
+fn create_patch(size: ImgSize) -> ImgBuf<[u8; 4]> {
+    let pixels = (0..size.x*size.y)
+        .map(|index| {
+            let (x, y) = (index % size.x, index / size.x);
+            [(x % 256) as u8, (y % 256) as u8, ((x*y) % 256) as u8, 255]
+        })
+        .collect();
+
+    ImgBuf::from_vec(size, pixels)
+}
@@ -0,0 +1,58 @@
+//! GTK application running GUI, logic and compositor threads
+
+use gtk::*;
+use gio::{ApplicationFlags, ApplicationExt, ApplicationExtManual};
+use std::sync::{mpsc, Arc, atomic::AtomicU64};
+use log::*;
+use crate::common::{start_thread_loop, convert_err};
+use crate::gui::build_ui;
+use crate::message::{CompositeMessage, LogicMessage};
+use crate::logic::LogicState;
+use crate::composite::CompositorState;
+
+const APP_NAME: &str = "nanomosaic.gtk";
+
+pub fn start_application(file_name: Option<String>) -> Result<(), String> {
+    info!("Starting {}", APP_NAME);
+    info!("Input image: {:?}", &file_name);
+
+    let queue_size = 3;
+
+    let app = Application::new(APP_NAME, ApplicationFlags::NON_UNIQUE)
+        .expect("Initialization failed...");
+
+    let (logic_tx, logic_rx) = mpsc::sync_channel::<Option<LogicMessage>>(queue_size);
+    let (composite_tx, composite_rx) = mpsc::sync_channel::<Option<CompositeMessage>>(queue_size);
+
+    let latest_generation = Arc::new(AtomicU64::new(0));
+    let logic = LogicState::new(composite_tx.clone(), latest_generation.clone());
+    let compositor = CompositorState::new(logic_tx.clone(), latest_generation);
+
+    let state_thread = start_thread_loop(logic_rx, logic);
+    let compositor_thread = start_thread_loop(composite_rx, compositor);
+
+    let gui_logic_tx = logic_tx.clone();
+    let gui_composite_tx = composite_tx.clone();
+
+    app.connect_startup(move |app|
+        build_ui(
+            app,
+            file_name.clone(),
+            gui_logic_tx.clone(),
+            gui_composite_tx.clone(),
+        )
+    );
+    
+    app.connect_activate(|_| {});
+    app.run(&vec![]);
+
+    convert_err(logic_tx.send(None))?;
+    convert_err(state_thread.join())?;
+    debug!("Logic thread finished");
+
+    convert_err(composite_tx.send(None))?;
+    convert_err(compositor_thread.join())?;
+    debug!("Compositor thread finished");
+
+    Ok(())
+}
\ No newline at end of file
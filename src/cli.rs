@@ -0,0 +1,199 @@
+//! Command line interface, including headless rendering without GTK
+
+use log::*;
+use std::str::FromStr;
+use nanocv::{Range2d, Img};
+use nanomosaic::{
+    load_image, select_patch, save_mosaic, refine_selection, 
+    PatternId, MosaicOptions, OutputOptions, OutputKind, Filter, Project, SeamBlend, Named,
+};
+
+pub const USAGE: &str = "USAGE:
+nanomosaic [image]
+nanomosaic render [--project file] --input <image> --output <image> [--rect x,y,w,h]
+    [--kind mosaic|texture|cell] [--sidecar yes|no]
+    [--pattern name]
+    [--segments count] [--repeat XxY]
+    [--width px] [--height px] [--width-mm mm] [--height-mm mm] [--dpi dpi]
+    [--filter nearest|bilinear|bicubic|lanczos]
+    [--refine position|size]
+    [--blend none|cross-fade|gradient] [--blend-width px]
+
+Patterns: mirror, mirror-x, mirror-y, rotation, translation, point-reflection,
+    kaleidoscope
+Wallpaper groups: p1, p2, pm, pg, cm, pmm, pmg, pgg, cmm, p4, p4m, p4g,
+    p3, p3m1, p31m, p6, p6m";
+
+pub enum Command {
+    /// Start GTK user interface, optionally loading an image
+    Gui(Option<String>),
+    /// Render mosaic into a file without opening a window
+    Render(RenderArgs),
+    /// Print usage information
+    Help,
+}
+
+pub struct RenderArgs {
+    pub input: String,
+    pub output_path: String,
+    /// Selected area as x, y, width and height, whole image if not specified
+    pub rect: Option<(isize, isize, isize, isize)>,
+    pub options: MosaicOptions,
+    pub output: OutputOptions,
+    /// Search for rectangle with least visible seams, size is refined if true
+    pub refine: Option<bool>,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    match args.first().map(|arg| arg.as_str()) {
+        None => Ok(Command::Gui(None)),
+        Some("render") => Ok(Command::Render(parse_render_args(&args[1..])?)),
+        Some("--help") | Some("-h") => Ok(Command::Help),
+        Some(path) if args.len() == 1 => Ok(Command::Gui(Some(path.to_owned()))),
+        _ => Err(format!("Wrong parameters provided.")),
+    }
+}
+
+fn parse_render_args(args: &[String]) -> Result<RenderArgs, String> {
+    let mut input = None;
+    let mut output_path = None;
+    let mut rect = None;
+    let mut options = MosaicOptions::default();
+    let mut output = OutputOptions::default();
+    let (mut width_mm, mut height_mm) = (None, None);
+    let mut refine = None;
+    let mut iter = args.iter();
+
+    // Project settings are loaded first so that other parameters can override them
+    if let Some(path) = project_path(args) {
+        let project = Project::load(path)?;
+        input = Some(project.source.clone());
+        rect = Some(project.rect());
+        options = project.mosaic;
+        output = project.output;
+    }
+
+    while let Some(arg) = iter.next() {
+        let value = iter.next().ok_or(format!("Missing value for {}", arg))?;
+
+        match arg.as_str() {
+            "--project" => {},
+            "--input" => input = Some(value.clone()),
+            "--output" => output_path = Some(value.clone()),
+            "--rect" => rect = Some(parse_rect(value)?),
+            "--pattern" => options.pattern = PatternId::from_name(value)
+                .ok_or(format!("Unknown pattern {}", value))?,
+            "--segments" => options.segments = parse_number(value)?,
+            "--repeat" => options.repeat = parse_pair(value)?,
+            "--width" => output.width = Some(parse_number(value)?),
+            "--height" => output.height = Some(parse_number(value)?),
+            "--width-mm" => width_mm = Some(parse_number(value)?),
+            "--height-mm" => height_mm = Some(parse_number(value)?),
+            "--dpi" => output.dpi = Some(parse_number(value)?),
+            "--filter" => options.filter = Filter::from_name(value)
+                .ok_or(format!("Unknown filter {}", value))?,
+            "--kind" => output.kind = OutputKind::from_name(value)
+                .ok_or(format!("Unknown output kind {}", value))?,
+            "--sidecar" => output.sidecar = parse_bool(value)?,
+            "--refine" => refine = Some(parse_refine(value)?),
+            "--blend" => options.blend = SeamBlend::from_name(value)
+                .ok_or(format!("Unknown seam blend {}", value))?,
+            "--blend-width" => options.blend_width = parse_number(value)?,
+            _ => return Err(format!("Unknown parameter {}", arg)),
+        }
+    }
+
+    if width_mm.is_some() || height_mm.is_some() {
+        let dpi = output.dpi.ok_or("Physical size requires --dpi parameter")?;
+        output = OutputOptions {
+            kind: output.kind,
+            sidecar: output.sidecar,
+            ..OutputOptions::from_physical(width_mm, height_mm, dpi)
+        };
+    }
+
+    Ok(RenderArgs {
+        input: input.ok_or("Missing --input parameter")?,
+        output_path: output_path.ok_or("Missing --output parameter")?,
+        rect,
+        options,
+        output,
+        refine,
+    })
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err(format!("Value must be yes or no, got {}", value)),
+    }
+}
+
+fn parse_refine(value: &str) -> Result<bool, String> {
+    match value {
+        "position" => Ok(false),
+        "size" => Ok(true),
+        _ => Err(format!("Refine must be position or size, got {}", value)),
+    }
+}
+
+fn parse_pair<T: FromStr>(value: &str) -> Result<(T, T), String> {
+    let mut parts = value.split('x');
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(x), Some(y), None) => Ok((parse_number(x)?, parse_number(y)?)),
+        _ => Err(format!("Value must be specified as XxY, got {}", value)),
+    }
+}
+
+fn parse_number<T: FromStr>(value: &str) -> Result<T, String> {
+    value.trim().parse::<T>().map_err(|_| format!("Invalid number {}", value))
+}
+
+fn project_path(args: &[String]) -> Option<&String> {
+    let position = args.iter().position(|arg| arg == "--project")?;
+    args.get(position + 1)
+}
+
+fn parse_rect(value: &str) -> Result<(isize, isize, isize, isize), String> {
+    let numbers = value.split(',')
+        .map(|item| item.trim().parse::<isize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("Rectangle must be specified as x,y,w,h, got {}", value))?;
+
+    match numbers.as_slice() {
+        &[x, y, w, h] if w > 0 && h > 0 => Ok((x, y, w, h)),
+        _ => Err(format!("Rectangle must be specified as x,y,w,h, got {}", value)),
+    }
+}
+
+pub fn render(args: &RenderArgs) -> Result<(), String> {
+    let image = load_image(&args.input)?;
+    info!("Image {} x {} loaded", image.width(), image.height());
+
+    let (w, h) = (image.width() as isize, image.height() as isize);
+    let (x, y, width, height) = args.rect.unwrap_or((0, 0, w, h));
+
+    if x < 0 || y < 0 || x + width > w || y + height > h {
+        return Err(format!(
+            "Rectangle {},{},{},{} does not fit into image {} x {}",
+            x, y, width, height, w, h
+        ));
+    }
+
+    let (x, y, width, height) = match args.refine {
+        Some(resize) => {
+            let pattern = args.options.create_pattern();
+            let rect = refine_selection(&image, pattern.as_ref(), (x, y, width, height), resize);
+            info!("Selection refined to {},{},{},{}", rect.0, rect.1, rect.2, rect.3);
+            rect
+        },
+        None => (x, y, width, height),
+    };
+
+    let patch = select_patch(&image, Range2d::new(x..x + width, y..y + height));
+    save_mosaic(&patch, &args.options, &args.output, &args.output_path)?;
+    info!("Mosaic saved into {}", &args.output_path);
+    Ok(())
+}
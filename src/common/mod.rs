@@ -2,12 +2,16 @@
 
 mod panic;
 mod logger;
+#[cfg(feature = "gui")]
 mod utils;
+#[cfg(feature = "gui")]
 mod threads;
-mod image;
 
 pub use self::panic::set_logging_panic_hook;
-pub use self::image::{resize, resize_factor};
 pub use self::logger::init_simple_logger;
+#[cfg(feature = "gui")]
+pub use nanomosaic::{resize, resize_factor};
+#[cfg(feature = "gui")]
 pub use self::threads::start_thread_loop;
-pub use self::utils::{convert_err, log_err};
\ No newline at end of file
+#[cfg(feature = "gui")]
+pub use self::utils::{log_err, convert_err};
\ No newline at end of file
@@ -2,30 +2,41 @@
 
 use log::*;
 use glib::{Sender as GlibSender};
-use image::RgbaImage;
 use crate::message::*;
-use crate::common::{resize, convert_err};
-use nanocv::{ImgBuf, ImgSize, Img, Vec2d};
-use nanocv::filter::{map_range, mirror_horizontal_new, mirror_vertical_new};
+use crate::common::resize;
+use nanocv::{ImgBuf, ImgSize, Img};
+use std::{cmp::max, sync::{Mutex, atomic::Ordering}};
+use nanomosaic::{
+    render_mosaic_view, save_mosaic_with_progress, heal_seams, MosaicOptions, OutputOptions, 
+    SaveProgress, SaveStage, SeamBlend,
+};
+
+/// Patch, its blending settings and the blended patch
+type BlendedPatch = (ImgBuf<Rgba>, (SeamBlend, usize), ImgBuf<Rgba>);
 
 pub struct CompositorState { 
     logic: LogicSender,
     gui: Option<GlibSender<GuiMessage>>,    
+    latest_generation: LatestGeneration,
+    /// Last full resolution patch with blended seams
+    blended: Option<BlendedPatch>,
 }
 
 impl MessageReceiver<CompositeMessage> for CompositorState {
     fn receive(&mut self, message: CompositeMessage) -> Result<(), String> {
         match message {
             CompositeMessage::InitGui(channel) => Ok(self.init_gui(channel)),
-            CompositeMessage::CompositeMosaic((img, size)) => Ok(self.composite(img, size)),
-            CompositeMessage::SaveMosaic((img, path)) => self.save_mosaic(&img, &path),
+            CompositeMessage::CompositeMosaic((generation, img, view, options)) => 
+                Ok(self.composite(generation, img, view, options)),
+            CompositeMessage::SaveMosaic((img, path, options, output, cancel)) => 
+                self.save_mosaic(&img, &path, options, output, cancel),
         }
     }
 }
 
 impl CompositorState {
-    pub fn new(logic: LogicSender) -> Self {
-        Self {logic, gui: None}
+    pub fn new(logic: LogicSender, latest_generation: LatestGeneration) -> Self {
+        Self {logic, gui: None, latest_generation, blended: None}
     }    
 
     fn init_gui(&mut self, channel: GlibSender<GuiMessage>) {
@@ -33,69 +44,142 @@ impl CompositorState {
         debug!("Compositor: GUI channel initialized.")        
     }    
 
-    fn composite(&self, img: ImgBuf<Rgba>, size: ImgSize) {
-        let resized = resize(&img, size/2);
-        let mosaic = create_mosaic(&resized);
-        send_glib(&self.gui, GuiMessage::RenderTarget(mosaic));        
-        send(&self.logic, LogicMessage::CompositorFinished)
+    fn composite(
+        &mut self, generation: u64, img: ImgBuf<Rgba>, view: MosaicView, options: MosaicOptions
+    ) {
+        if let Some(mosaic) = self.render_preview(generation, img, view, options) {
+            send_glib(&self.gui, GuiMessage::RenderTarget(mosaic));
+        } else {
+            debug!("Compositor: generation {} cancelled", generation);
+        }
+
+        send(&self.logic, LogicMessage::CompositorFinished(generation))
     }
 
-    fn save_mosaic(&self, img: &ImgBuf<Rgba>, path: &str) -> Result<(), String> {
-        let result = save(img, &path);
-    
-        if let Err(ref message) = result {
-            send_glib(&self.gui, GuiMessage::ShowError(format!(
-                "Could not save image into:\n{}\nPerhaps image format is not specified?\n{}",
-                path, message
-            )));
+    /// Renders preview, returns None if a newer preview has been requested
+    fn render_preview(
+        &mut self, generation: u64, img: ImgBuf<Rgba>, view: MosaicView, options: MosaicOptions
+    ) -> Option<ImgBuf<Rgba>> {
+        let latest = self.latest_generation.clone();
+        let cancelled = || latest.load(Ordering::SeqCst) != generation;
+
+        if cancelled() { return None; }
+
+        let pattern = options.create_pattern();
+
+        // Reduced patch is filtered properly and its seams are blended 
+        // in the reduced resolution, magnified mosaic is sampled 
+        // from the blended patch in full resolution
+        let (patch, origin, scale) = match view.scale < 1.0 {
+            true => {
+                let blend = options.seam_blend();
+                let blended = blend.blended_size(img.size(), options.blend_width);
+                let patch_size = ImgSize::new(
+                    max(1, (img.width() as f64*view.scale) as usize), 
+                    max(1, (img.height() as f64*view.scale) as usize)
+                );
+                let resized = resize(&img, patch_size, options.filter);
+                let factor = resized.width() as f64/img.width() as f64;
+                let width = max(1, (options.blend_width as f64*factor).round() as usize);
+                let resized = heal_seams(&resized, blend, width).unwrap_or(resized);
+                let factor = resized.width() as f64/max(1, blended.x) as f64;
+                let origin = (view.origin.0*factor, view.origin.1*factor);
+                (resized, origin, view.scale/factor)
+            },
+            false => (self.blended_patch(img, &options), view.origin, view.scale),
         };
-    
-        result
-    }    
-}
 
-fn save(img: &ImgBuf<Rgba>, path: &str) -> Result<(), String> {
-    let mosaic = create_mosaic(img);
-    let size = mosaic.size();
-    let pixels = mosaic.into_vec();
-    let bytes = rgba_to_bytes(pixels);
+        if cancelled() { return None; }
 
-    let result = RgbaImage::from_vec(size.x as u32, size.y as u32, bytes)
-        .ok_or("Could not allocate image data")?;
+        render_mosaic_view(&patch, pattern.as_ref(), origin, scale, view.size, &cancelled)
+    }
 
-    convert_err(result.save(path))
-}    
+    /// Patch with blended seams in full resolution, the last result is reused
+    /// while the patch and blending settings do not change
+    fn blended_patch(&mut self, img: ImgBuf<Rgba>, options: &MosaicOptions) -> ImgBuf<Rgba> {
+        let settings = (options.seam_blend(), options.blend_width);
 
-fn create_mosaic(image: &ImgBuf<Rgba>) -> ImgBuf<Rgba> {
-    let mut result = ImgBuf::new(image.size()*2);
-    let mirror_x = mirror_horizontal_new(image);
-    let mirror_y = mirror_vertical_new(image);
-    let mirror_xy = mirror_vertical_new(&mirror_x);
-    let src = image.range();
-    let (w, h) = (image.width() as isize, image.height() as isize);
+        if settings.0 == SeamBlend::None {
+            return img;
+        }
+
+        if let Some((ref source, cached, ref blended)) = self.blended {
+            if cached == settings && same_pixels(source, &img) {
+                return blended.clone();
+            }
+        }
+
+        let blended = options.blend_patch(&img).unwrap_or_else(|| img.clone());
+        self.blended = Some((img, settings, blended.clone()));
+        blended
+    }
+
+    fn save_mosaic(
+        &self, 
+        img: &ImgBuf<Rgba>, 
+        path: &str, 
+        options: MosaicOptions, 
+        output: OutputOptions,
+        cancel: SaveCancel,
+    ) -> Result<(), String> {
+        let monitor = SaveMonitor::new(self.gui.clone(), cancel.clone());
+        let result = save_mosaic_with_progress(img, &options, &output, &path, &monitor);
+
+        let outcome = match (&result, cancel.load(Ordering::SeqCst)) {
+            (Ok(()), _) => SaveOutcome::Saved(path.to_owned()),
+            (Err(_), true) => SaveOutcome::Cancelled,
+            (Err(message), false) => {
+                send_glib(&self.gui, GuiMessage::ShowError(format!(
+                    "Could not save image into:\n{}\nPerhaps image format is not specified?\n{}",
+                    path, message
+                )));
+                SaveOutcome::Failed
+            },
+        };
 
-    map_range(image, &mut result, src, src, |x, _| x);
-    map_range(&mirror_x, &mut result, src, src + Vec2d::new(w, 0), |x, _| x);
-    map_range(&mirror_y, &mut result, src, src + Vec2d::new(0, h), |x, _| x);
-    map_range(&mirror_xy, &mut result, src, src + Vec2d::new(w, h), |x, _| x);
+        send_glib(&self.gui, GuiMessage::SaveFinished(outcome));
 
-    result
+        match cancel.load(Ordering::SeqCst) {
+            true => Ok(()),
+            false => result,
+        }
+    }    
+}
+
+fn same_pixels(first: &ImgBuf<Rgba>, second: &ImgBuf<Rgba>) -> bool {
+    first.size() == second.size() 
+        && (0..first.height()).all(|y| first.line_ref(y) == second.line_ref(y))
+}
+
+/// Forwards save progress to the GUI and reads its cancel flag, 
+/// progress is sent only when whole percents change
+struct SaveMonitor {
+    gui: Mutex<Option<GlibSender<GuiMessage>>>,
+    cancel: SaveCancel,
+    last: Mutex<Option<(SaveStage, u32)>>,
 }
 
-fn rgba_to_bytes(input: Vec<Rgba>) -> Vec<u8> {
-    let count = input.len()*4;
-    let mut result = vec![0u8; count];
-    let mut offset = 0;
+impl SaveMonitor {
+    fn new(gui: Option<GlibSender<GuiMessage>>, cancel: SaveCancel) -> Self {
+        Self {gui: Mutex::new(gui), cancel, last: Mutex::new(None)}
+    }
+}
+
+impl SaveProgress for SaveMonitor {
+    fn progress(&self, stage: SaveStage, done: f64) {
+        let percent = (done.max(0.0).min(1.0)*100.0) as u32;
 
-    while offset < count {
-        let input = input[offset/4];
-        result[offset + 0] = input[0];
-        result[offset + 1] = input[1];
-        result[offset + 2] = input[2];
-        result[offset + 3] = input[3];
+        if let Ok(mut last) = self.last.lock() {
+            if *last == Some((stage, percent)) { return; }
+            *last = Some((stage, percent));
+        }
 
-        offset += 4;
+        if let Ok(gui) = self.gui.lock() {
+            send_glib(&gui, GuiMessage::SaveProgress((stage, done)));
+        }
     }
 
-    result
+    fn cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
 }
@@ -0,0 +1,260 @@
+//! Seam blending making patch borders continuous when the patch is tiled
+
+use nanocv::{ImgBuf, ImgSize, Img};
+use rustfft::{FFTplanner, num_complex::Complex};
+use serde::{Serialize, Deserialize};
+use std::f64::consts::PI;
+use super::image::Rgba;
+
+/// Method blending opposite patch borders, stored in project files
+/// by its short name
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum SeamBlend {
+    None,
+    /// Overlapping borders of given width are cross-faded, 
+    /// patch is reduced by the border width
+    CrossFade,
+    /// Smooth component is removed from the patch so that only the periodic
+    /// component remains, colour gradients inside the patch are preserved
+    Gradient,
+}
+
+named_enum!(SeamBlend, "seam blend", [
+    None => ("none", "None"),
+    CrossFade => ("cross-fade", "Cross-fade"),
+    Gradient => ("gradient", "Gradient domain"),
+]);
+
+impl SeamBlend {
+    /// Size of the patch after blending
+    pub fn blended_size(self, patch: ImgSize, width: usize) -> ImgSize {
+        match self {
+            SeamBlend::CrossFade => {
+                let (width_x, width_y) = border_width(patch, width);
+                ImgSize::new(patch.x - width_x, patch.y - width_y)
+            },
+            _ => patch,
+        }
+    }
+}
+
+/// Makes the patch seamlessly tileable by translation, `width` is 
+/// the cross-faded border width in pixels, returns None if blending is off
+pub fn heal_seams(image: &ImgBuf<Rgba>, blend: SeamBlend, width: usize) -> Option<ImgBuf<Rgba>> {
+    match blend {
+        SeamBlend::None => None,
+        SeamBlend::CrossFade => {
+            let (width_x, width_y) = border_width(image.size(), width);
+            Some(cross_fade_y(&cross_fade_x(image, width_x), width_y))
+        },
+        SeamBlend::Gradient => Some(periodic_component(image)),
+    }
+}
+
+/// Creates seamless texture shifted by half of the patch size, so that
+/// the patch inside is at texture borders and the seams are in the centre.
+/// Healing wrapped patch borders before the shift is equivalent 
+/// to healing the seams exposed in the centre after it.
+pub fn offset_and_heal(image: &ImgBuf<Rgba>, blend: SeamBlend, width: usize) -> ImgBuf<Rgba> {
+    let healed = heal_seams(image, blend, width);
+    offset_half(healed.as_ref().unwrap_or(image))
+}
+
+/// Shifts image by half of its size wrapping around its borders
+fn offset_half(image: &ImgBuf<Rgba>) -> ImgBuf<Rgba> {
+    let (width, height) = (image.width(), image.height());
+    let (dx, dy) = (width/2, height/2);
+    let mut pixels = Vec::with_capacity(width*height);
+
+    for y in 0..height {
+        let line = image.line_ref((y + dy) % height);
+        pixels.extend_from_slice(&line[dx..]);
+        pixels.extend_from_slice(&line[..dx]);
+    }
+
+    ImgBuf::from_vec(image.size(), pixels)
+}
+
+/// Border width limited to half of the patch
+fn border_width(patch: ImgSize, width: usize) -> (usize, usize) {
+    (width.min(patch.x/2), width.min(patch.y/2))
+}
+
+/// Blends right border over the left one
+fn cross_fade_x(image: &ImgBuf<Rgba>, border: usize) -> ImgBuf<Rgba> {
+    let (width, height) = (image.width() - border, image.height());
+    let mut pixels = Vec::with_capacity(width*height);
+
+    for y in 0..height {
+        let line = image.line_ref(y);
+
+        for x in 0..width {
+            pixels.push(match x < border {
+                true => mix(line[x + width], line[x], (x as f32 + 0.5)/border as f32),
+                false => line[x],
+            });
+        }
+    }
+
+    ImgBuf::from_vec(ImgSize::new(width, height), pixels)
+}
+
+/// Blends bottom border over the top one
+fn cross_fade_y(image: &ImgBuf<Rgba>, border: usize) -> ImgBuf<Rgba> {
+    let (width, height) = (image.width(), image.height() - border);
+    let mut pixels = Vec::with_capacity(width*height);
+
+    for y in 0..height {
+        let line = image.line_ref(y);
+
+        match y < border {
+            true => {
+                let overlap = image.line_ref(y + height);
+                let weight = (y as f32 + 0.5)/border as f32;
+
+                for x in 0..width {
+                    pixels.push(mix(overlap[x], line[x], weight));
+                }
+            },
+            false => pixels.extend_from_slice(line),
+        }
+    }
+
+    ImgBuf::from_vec(ImgSize::new(width, height), pixels)
+}
+
+fn mix(a: Rgba, b: Rgba, weight: f32) -> Rgba {
+    let mut result = [0u8; 4];
+
+    for channel in 0..4 {
+        let value = a[channel] as f32*(1.0 - weight) + b[channel] as f32*weight;
+        result[channel] = value.round() as u8;
+    }
+
+    result
+}
+
+/// Periodic plus smooth decomposition (L. Moisan, 2011), smooth component
+/// is computed from border discontinuities in the frequency domain
+fn periodic_component(image: &ImgBuf<Rgba>) -> ImgBuf<Rgba> {
+    let (width, height) = (image.width(), image.height());
+    let mut pixels = Vec::with_capacity(width*height);
+
+    for y in 0..height {
+        pixels.extend_from_slice(image.line_ref(y));
+    }
+
+    if width < 2 || height < 2 {
+        return ImgBuf::from_vec(image.size(), pixels);
+    }
+
+    let mut smooth = vec![Complex::new(0.0, 0.0); width*height];
+
+    for channel in 0..4 {
+        let value = |x: usize, y: usize| pixels[y*width + x][channel] as f64;
+
+        for item in smooth.iter_mut() {
+            *item = Complex::new(0.0, 0.0);
+        }
+
+        for y in 0..height {
+            let difference = value(width - 1, y) - value(0, y);
+            smooth[y*width].re += difference;
+            smooth[y*width + width - 1].re -= difference;
+        }
+
+        for x in 0..width {
+            let difference = value(x, height - 1) - value(x, 0);
+            smooth[x].re += difference;
+            smooth[(height - 1)*width + x].re -= difference;
+        }
+
+        fft_2d(&mut smooth, width, height, false);
+
+        for r in 0..height {
+            for q in 0..width {
+                let denominator = 2.0*(2.0*PI*q as f64/width as f64).cos() 
+                    + 2.0*(2.0*PI*r as f64/height as f64).cos() - 4.0;
+
+                smooth[r*width + q] = match q == 0 && r == 0 {
+                    true => Complex::new(0.0, 0.0),
+                    false => smooth[r*width + q]/denominator,
+                };
+            }
+        }
+
+        fft_2d(&mut smooth, width, height, true);
+        let normalization = (width*height) as f64;
+
+        for (pixel, component) in pixels.iter_mut().zip(smooth.iter()) {
+            let value = pixel[channel] as f64 - component.re/normalization;
+            pixel[channel] = value.round().max(0.0).min(255.0) as u8;
+        }
+    }
+
+    ImgBuf::from_vec(image.size(), pixels)
+}
+
+/// Unnormalized 2D discrete Fourier transform of row major data
+fn fft_2d(data: &mut [Complex<f64>], width: usize, height: usize, inverse: bool) {
+    let mut planner = FFTplanner::new(inverse);
+    let row_fft = planner.plan_fft(width);
+    let mut output = vec![Complex::new(0.0, 0.0); width];
+
+    for row in data.chunks_mut(width) {
+        row_fft.process(row, &mut output);
+        row.copy_from_slice(&output);
+    }
+
+    let column_fft = planner.plan_fft(height);
+    let mut column = vec![Complex::new(0.0, 0.0); height];
+    let mut output = vec![Complex::new(0.0, 0.0); height];
+
+    for x in 0..width {
+        for y in 0..height {
+            column[y] = data[y*width + x];
+        }
+
+        column_fft.process(&mut column, &mut output);
+
+        for y in 0..height {
+            data[y*width + x] = output[y];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(size: ImgSize, value: impl Fn(usize, usize) -> u8) -> ImgBuf<Rgba> {
+        let pixels = (0..size.y)
+            .flat_map(|y| (0..size.x).map(move |x| (x, y)))
+            .map(|(x, y)| [value(x, y), value(y, x), 128, 255])
+            .collect();
+
+        ImgBuf::from_vec(size, pixels)
+    }
+
+    /// Largest difference between pixels on the opposite borders
+    fn border_jump(image: &ImgBuf<Rgba>) -> i32 {
+        let (width, height) = (image.width(), image.height());
+        let difference = |a: Rgba, b: Rgba| {
+            (0..4).map(|c| (a[c] as i32 - b[c] as i32).abs()).max().unwrap_or(0)
+        };
+
+        let (top, bottom) = (image.line_ref(0), image.line_ref(height - 1));
+        let rows = (0..height).map(|y| image.line_ref(y))
+            .map(|line| difference(line[0], line[width - 1]));
+        let columns = (0..width).map(|x| difference(top[x], bottom[x]));
+        rows.chain(columns).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn periodic_component_matches_opposite_borders() {
+        let ramp = image(ImgSize::new(32, 24), |x, y| (x*4 + y*3) as u8);
+        assert!(border_jump(&ramp) > 100);
+        assert!(border_jump(&periodic_component(&ramp)) <= 5);
+    }
+}
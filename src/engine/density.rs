@@ -0,0 +1,145 @@
+//! Writing pixel density into encoded PNG and JPEG files
+
+const METERS_PER_INCH: f64 = 0.0254;
+const PNG_SIGNATURE_SIZE: usize = 8;
+const PNG_IHDR_CHUNK_SIZE: usize = 25;
+
+/// Inserts pHYs chunk with pixels per meter right after PNG header chunk
+pub fn set_png_density(data: &mut Vec<u8>, dpi: f64) -> Result<(), String> {
+    let offset = PNG_SIGNATURE_SIZE + PNG_IHDR_CHUNK_SIZE;
+
+    if data.len() < offset || &data[12..16] != b"IHDR" {
+        return Err(format!("Invalid PNG data, header chunk not found"));
+    }
+
+    let mut chunk = Vec::with_capacity(21);
+    chunk.extend_from_slice(&9u32.to_be_bytes());
+    chunk.extend_from_slice(b"pHYs");
+    chunk.extend_from_slice(&png_density_data(dpi));
+    let crc = crc32(&chunk[4..]);
+    chunk.extend_from_slice(&crc.to_be_bytes());
+
+    data.splice(offset..offset, chunk);
+    Ok(())
+}
+
+/// Contents of PNG pHYs chunk with pixels per meter in both directions
+pub fn png_density_data(dpi: f64) -> [u8; 9] {
+    let ppm = (dpi/METERS_PER_INCH).round() as u32;
+    let mut data = [0u8; 9];
+    data[0..4].copy_from_slice(&ppm.to_be_bytes());
+    data[4..8].copy_from_slice(&ppm.to_be_bytes());
+    // Unit specifier, 1 means meters
+    data[8] = 1;
+    data
+}
+
+/// Writes density in dots per inch into JFIF header of a JPEG file,
+/// the header is created if the encoder did not write one
+pub fn set_jpeg_density(data: &mut Vec<u8>, dpi: f64) -> Result<(), String> {
+    if data.len() < 2 || data[0] != 0xFF || data[1] != 0xD8 {
+        return Err(format!("Invalid JPEG data, start of image not found"));
+    }
+
+    let density = (dpi.round() as u32).min(u16::max_value() as u32) as u16;
+
+    if data.len() >= 18 && data[2] == 0xFF && data[3] == 0xE0 && &data[6..11] == b"JFIF\0" {
+        // Units 1 mean dots per inch
+        data[13] = 1;
+        data[14..16].copy_from_slice(&density.to_be_bytes());
+        data[16..18].copy_from_slice(&density.to_be_bytes());
+    } else {
+        let mut segment = vec![0xFF, 0xE0, 0x00, 0x10];
+        segment.extend_from_slice(b"JFIF\0");
+        segment.extend_from_slice(&[0x01, 0x02, 0x01]);
+        segment.extend_from_slice(&density.to_be_bytes());
+        segment.extend_from_slice(&density.to_be_bytes());
+        segment.extend_from_slice(&[0x00, 0x00]);
+        data.splice(2..2, segment);
+    }
+
+    Ok(())
+}
+
+/// CRC-32 as used by PNG chunks
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+
+    for &byte in data {
+        crc ^= byte as u32;
+
+        for _ in 0..8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageOutputFormat, RgbImage, RgbaImage};
+
+    fn encode(image: DynamicImage, format: ImageOutputFormat) -> Vec<u8> {
+        let mut data = Vec::new();
+        image.write_to(&mut data, format).unwrap();
+        data
+    }
+
+    /// Density stored in JFIF header as units and X, Y density
+    fn jfif_density(data: &[u8]) -> (u8, u16, u16) {
+        assert_eq!(&data[6..11], b"JFIF\0");
+        let x = u16::from_be_bytes([data[14], data[15]]);
+        let y = u16::from_be_bytes([data[16], data[17]]);
+        (data[13], x, y)
+    }
+
+    #[test]
+    fn crc32_matches_png_chunk_crc() {
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+
+    #[test]
+    fn png_density_is_decoded() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::new(3, 2));
+        let mut data = encode(image, ImageOutputFormat::Png);
+        set_png_density(&mut data, 300.0).unwrap();
+
+        let (_, reader) = png::Decoder::new(&data[..]).read_info().unwrap();
+        let dims = reader.info().pixel_dims.expect("pHYs chunk not found");
+        assert_eq!((dims.xppu, dims.yppu), (11811, 11811));
+        assert_eq!(dims.unit, png::Unit::Meter);
+
+        let decoded = image::load_from_memory(&data).unwrap().to_rgba();
+        assert_eq!(decoded.dimensions(), (3, 2));
+    }
+
+    #[test]
+    fn jpeg_density_is_decoded() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(8, 8));
+        let mut data = encode(image, ImageOutputFormat::Jpeg(90));
+        set_jpeg_density(&mut data, 300.0).unwrap();
+
+        assert_eq!(jfif_density(&data), (1, 300, 300));
+        let decoded = image::load_from_memory(&data).unwrap().to_rgb();
+        assert_eq!(decoded.dimensions(), (8, 8));
+    }
+
+    #[test]
+    fn jfif_header_is_inserted_if_missing() {
+        let mut data = vec![0xFF, 0xD8, 0xFF, 0xD9];
+        set_jpeg_density(&mut data, 72.0).unwrap();
+
+        assert_eq!(&data[2..4], &[0xFF, 0xE0]);
+        assert_eq!(jfif_density(&data), (1, 72, 72));
+        assert_eq!(&data[data.len() - 2..], &[0xFF, 0xD9]);
+    }
+
+    #[test]
+    fn invalid_data_is_rejected() {
+        assert!(set_png_density(&mut vec![0u8; 10], 300.0).is_err());
+        assert!(set_jpeg_density(&mut vec![0u8; 10], 300.0).is_err());
+    }
+}
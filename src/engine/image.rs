@@ -0,0 +1,62 @@
+use nanocv::{ImgBuf, ImgSize, Img};
+use std::{cmp::max, mem::ManuallyDrop, slice};
+use super::resample::{resample, Filter};
+
+/// Pixel stored as four bytes, buffer of pixels has the same contiguous RGBA
+/// byte layout as decoded images and pixbufs, so it is converted without copying
+pub type Rgba = [u8; 4];
+
+/// Reinterprets pixels as RGBA bytes without copying
+pub(crate) fn rgba_into_bytes(pixels: Vec<Rgba>) -> Vec<u8> {
+    let mut pixels = ManuallyDrop::new(pixels);
+    let (ptr, len, capacity) = (pixels.as_mut_ptr(), pixels.len(), pixels.capacity());
+    // [u8; 4] has alignment of u8 and no padding, allocation size stays the same
+    unsafe { Vec::from_raw_parts(ptr as *mut u8, len*4, capacity*4) }
+}
+
+/// Reinterprets RGBA bytes as pixels, data are copied only when the allocation 
+/// is not a whole number of pixels, incomplete trailing pixel is dropped
+pub(crate) fn rgba_from_bytes(mut bytes: Vec<u8>) -> Vec<Rgba> {
+    bytes.truncate(bytes.len()/4*4);
+
+    if bytes.capacity() % 4 != 0 {
+        return bytes.chunks_exact(4).map(|c| [c[0], c[1], c[2], c[3]]).collect();
+    }
+
+    let mut bytes = ManuallyDrop::new(bytes);
+    let (ptr, len, capacity) = (bytes.as_mut_ptr(), bytes.len(), bytes.capacity());
+    unsafe { Vec::from_raw_parts(ptr as *mut Rgba, len/4, capacity/4) }
+}
+
+/// Views pixels as RGBA bytes
+pub(crate) fn rgba_as_bytes(pixels: &[Rgba]) -> &[u8] {
+    unsafe { slice::from_raw_parts(pixels.as_ptr() as *const u8, pixels.len()*4) }
+}
+
+/// Scales image to fit into target size keeping its aspect ratio
+pub fn resize(source: &ImgBuf<Rgba>, target_size: ImgSize, filter: Filter) -> ImgBuf<Rgba> {
+    if target_size.x == 0 || target_size.y == 0 || source.size().x == 0 || source.size().y == 0 {
+        return ImgBuf::new_init(ImgSize::new(1, 1), [0, 0, 0, 0]);
+    }
+
+    let factor = resize_factor(source.size(), target_size);
+
+    let target = ImgSize::new(
+        max(1, (source.width() as f64*factor) as usize),
+        max(1, (source.height() as f64*factor) as usize),
+    );
+
+    resample(source, target, filter)
+}
+
+pub fn resize_factor(actual: ImgSize, target: ImgSize) -> f64 {
+    if actual.x == 0 || actual.y == 0 {
+        return 1.0;
+    }
+
+    let factor_x = target.x as f64/actual.x as f64;
+    let factor_y = target.y as f64/actual.y as f64;
+    let factor = if factor_x > factor_y {factor_y} else {factor_x};
+
+    factor
+}
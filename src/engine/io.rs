@@ -0,0 +1,76 @@
+use image::{open, RgbaImage, DynamicImage, ImageFormat, ImageOutputFormat};
+use nanocv::{ImgBuf, ImgSize, Img};
+use std::{fs::{self, File}, io::Write};
+use super::{image::{Rgba, rgba_from_bytes, rgba_into_bytes}, utils::convert_err};
+use super::progress::{SaveProgress, SaveStage, check_cancelled};
+use super::density::{set_png_density, set_jpeg_density};
+
+/// Encoded images are written in chunks of this size to report progress
+const WRITE_CHUNK_SIZE: usize = 1 << 20;
+
+/// Loads image from file, format is guessed from file contents
+pub fn load_image(path: &str) -> Result<ImgBuf<Rgba>, String> {
+    let buf = convert_err(open(path))?.into_rgba();
+    let size = ImgSize::new(buf.width() as usize, buf.height() as usize);
+    Ok(ImgBuf::from_vec(size, rgba_from_bytes(buf.into_vec())))
+}
+
+/// Saves image into file, format is given by file name suffix,
+/// pixel density is written into file metadata if specified
+pub fn save_image(img: ImgBuf<Rgba>, path: &str, dpi: Option<f64>) -> Result<(), String> {
+    let data = encode_image(img, path, dpi)?;
+    convert_err(fs::write(path, data))
+}
+
+/// Encodes image into format given by file name suffix, 
+/// pixel density is written into metadata if specified
+pub fn encode_image(img: ImgBuf<Rgba>, path: &str, dpi: Option<f64>) -> Result<Vec<u8>, String> {
+    let size = img.size();
+    let bytes = rgba_into_bytes(img.into_vec());
+
+    let image = RgbaImage::from_vec(size.x as u32, size.y as u32, bytes)
+        .ok_or("Could not allocate image data")?;
+
+    let format = convert_err(ImageFormat::from_path(path))?;
+    let mut data = Vec::new();
+    let image = DynamicImage::ImageRgba8(image);
+    convert_err(image.write_to(&mut data, ImageOutputFormat::from(format)))?;
+
+    match (dpi, format) {
+        (None, _) => {},
+        (Some(dpi), ImageFormat::Png) => set_png_density(&mut data, dpi)?,
+        (Some(dpi), ImageFormat::Jpeg) => set_jpeg_density(&mut data, dpi)?,
+        (Some(_), _) => 
+            return Err(format!("Image format {:?} does not support pixel density", format)),
+    }
+
+    Ok(data)
+}
+
+/// Writes encoded image in chunks reporting progress, partially written
+/// file is removed when the save is cancelled or fails
+pub(crate) fn write_file(
+    data: &[u8], path: &str, progress: &dyn SaveProgress
+) -> Result<(), String> {
+    let result = write_chunks(data, path, progress);
+
+    if result.is_err() {
+        let _ = fs::remove_file(path);
+    }
+
+    result
+}
+
+fn write_chunks(data: &[u8], path: &str, progress: &dyn SaveProgress) -> Result<(), String> {
+    let mut file = convert_err(File::create(path))?;
+    let mut written = 0;
+
+    for chunk in data.chunks(WRITE_CHUNK_SIZE) {
+        check_cancelled(progress)?;
+        convert_err(file.write_all(chunk))?;
+        written += chunk.len();
+        progress.progress(SaveStage::Write, written as f64/data.len() as f64);
+    }
+
+    convert_err(file.sync_all())
+}
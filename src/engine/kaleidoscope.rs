@@ -0,0 +1,125 @@
+use nanocv::ImgSize;
+use std::cmp::max;
+use std::f64::consts::PI;
+use super::pattern::{MosaicPattern, fold};
+
+/// Polar pattern reflecting a wedge around the cell center, the patch
+/// width spans the wedge angle and the patch height spans its radius.
+/// Cell corners lie outside the inscribed circle, radius there is mirrored
+/// back into the patch as if the circle was surrounded by a mirror ring,
+/// so the corners repeat the outer part of the wedge instead of smearing
+/// its border pixels
+pub struct Kaleidoscope {
+    segments: u32,
+}
+
+impl Kaleidoscope {
+    /// Creates kaleidoscope with given number of segments, odd counts are
+    /// rounded up because neighbouring segments are mirrored
+    pub fn new(segments: u32) -> Self {
+        Self {segments: max(2, segments + segments % 2)}
+    }
+
+    pub fn segments(&self) -> u32 {
+        self.segments
+    }
+}
+
+impl MosaicPattern for Kaleidoscope {
+    fn cell_size(&self, patch: ImgSize) -> ImgSize {
+        ImgSize::new(patch.y*2, patch.y*2)
+    }
+
+    fn map_point(&self, patch: ImgSize, x: f64, y: f64) -> (f64, f64) {
+        let center = patch.y as f64;
+        let (dx, dy) = (x - center, y - center);
+        let segment = 2.0*PI/self.segments as f64;
+        let angle = dy.atan2(dx).rem_euclid(2.0*segment);
+        let angle = if angle > segment { 2.0*segment - angle } else { angle };
+        // Corner radius is at most center*√2, single fold keeps it in the patch
+        let radius = fold((dx*dx + dy*dy).sqrt(), center);
+        (angle/segment*patch.x as f64, radius)
+    }
+
+    fn interpolated(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CENTER: f64 = 30.0;
+
+    fn patch() -> ImgSize {
+        ImgSize::new(40, 30)
+    }
+
+    /// Points spread over the whole unit cell including its corners
+    fn points() -> Vec<(f64, f64)> {
+        (0..200).map(|i| i as f64).map(|i| (
+            (i*0.618_034 + 0.123).fract()*2.0*CENTER,
+            (i*0.414_214 + 0.071).fract()*2.0*CENTER,
+        )).collect()
+    }
+
+    fn assert_invariant(pattern: &Kaleidoscope, symmetry: impl Fn(f64, f64) -> (f64, f64)) {
+        for (x, y) in points() {
+            let (sx, sy) = symmetry(x, y);
+            let a = pattern.map_point(patch(), x, y);
+            let b = pattern.map_point(patch(), sx, sy);
+
+            assert!(
+                (a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6,
+                "({}, {}) maps to {:?} and ({}, {}) to {:?}", x, y, a, sx, sy, b
+            );
+        }
+    }
+
+    #[test]
+    fn cell_is_square_around_patch_radius() {
+        let pattern = Kaleidoscope::new(5);
+        let cell = pattern.cell_size(patch());
+        assert_eq!(pattern.segments(), 6);
+        assert_eq!((cell.x, cell.y), (60, 60));
+    }
+
+    #[test]
+    fn pattern_is_invariant_under_rotation_and_mirrors() {
+        let pattern = Kaleidoscope::new(6);
+        let segment = 2.0*PI/6.0;
+
+        // Rotation by a mirrored pair of segments
+        let rotation = |x: f64, y: f64| {
+            let (dx, dy) = (x - CENTER, y - CENTER);
+            let (sin, cos) = (2.0*segment).sin_cos();
+            (CENTER + dx*cos - dy*sin, CENTER + dx*sin + dy*cos)
+        };
+
+        // Reflection along the border between the first two segments
+        let mirror = |x: f64, y: f64| {
+            let (dx, dy) = (x - CENTER, y - CENTER);
+            let (sin, cos) = (2.0*segment).sin_cos();
+            (CENTER + dx*cos + dy*sin, CENTER + dx*sin - dy*cos)
+        };
+
+        assert_invariant(&pattern, rotation);
+        assert_invariant(&pattern, |x, y| (x, 2.0*CENTER - y));
+        assert_invariant(&pattern, mirror);
+    }
+
+    #[test]
+    fn corners_are_mirrored_into_patch() {
+        let pattern = Kaleidoscope::new(6);
+
+        for (x, y) in points() {
+            let (u, v) = pattern.map_point(patch(), x, y);
+            assert!((-1e-9..=40.0 + 1e-9).contains(&u), "x {} at ({}, {})", u, x, y);
+            assert!((-1e-9..=CENTER + 1e-9).contains(&v), "y {} at ({}, {})", v, x, y);
+        }
+
+        let (_, corner) = pattern.map_point(patch(), 0.0, 0.0);
+        assert!((corner - (2.0 - 2.0f64.sqrt())*CENTER).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,46 @@
+//! Image processing functions independent of the user interface
+
+#[macro_use]
+mod named;
+mod image;
+mod io;
+mod patch;
+mod mosaic;
+mod pattern;
+mod wallpaper;
+mod kaleidoscope;
+mod options;
+mod output;
+mod density;
+mod resample;
+mod project;
+mod blend;
+mod sidecar;
+mod stream;
+mod progress;
+mod refine;
+mod utils;
+
+pub use self::image::{
+    Rgba, resize, resize_factor,
+};
+pub use self::io::{load_image, save_image};
+pub use self::patch::select_patch;
+pub use self::mosaic::{
+    create_mosaic, create_texture, render_mosaic_view, save_mosaic, save_mosaic_with_progress,
+};
+pub use self::pattern::{
+    MosaicPattern, PointMapper, PatternId, 
+    Mirror, MirrorX, MirrorY, Rotation, Translation, PointReflection,
+};
+pub use self::wallpaper::WallpaperGroup;
+pub use self::kaleidoscope::Kaleidoscope;
+pub use self::options::MosaicOptions;
+pub use self::output::{OutputOptions, OutputKind, MM_PER_INCH, DEFAULT_DPI};
+pub use self::resample::{Filter, resample, resample_tileable};
+pub use self::project::{Project, PROJECT_SUFFIX};
+pub use self::refine::refine_selection;
+pub use self::blend::{SeamBlend, heal_seams, offset_and_heal};
+pub use self::sidecar::TilingMetadata;
+pub use self::progress::{SaveProgress, SaveStage, NoProgress};
+pub use self::named::Named;
@@ -0,0 +1,248 @@
+use nanocv::{ImgBuf, ImgSize, Img};
+use std::{cmp::max, sync::atomic::{AtomicUsize, Ordering}};
+use rayon::prelude::*;
+use super::{image::Rgba, io::{encode_image, write_file}, pattern::{MosaicPattern, PointMapper}};
+use super::{options::MosaicOptions, output::{OutputOptions, OutputKind}};
+use super::resample::resample_tileable;
+use super::{blend::offset_and_heal, sidecar::TilingMetadata};
+use super::stream::{supports_streaming, save_png_stream};
+use super::utils::clamp_index;
+use super::progress::{SaveProgress, SaveStage, NoProgress, check_cancelled};
+
+/// Number of composed rows between two progress reports
+const PROGRESS_ROWS: usize = 64;
+
+/// Creates mosaic, texture or unit cell according to output kind and saves it
+/// into file, image is resampled if output size differs from its native resolution.
+/// Mosaics saved into PNG in native resolution are encoded row by row 
+/// without composing the whole image in memory.
+pub fn save_mosaic(
+    img: &ImgBuf<Rgba>, 
+    options: &MosaicOptions,
+    output: &OutputOptions,
+    path: &str
+) -> Result<(), String> {
+    save_mosaic_with_progress(img, options, output, path, &NoProgress)
+}
+
+/// Saves mosaic the same way as `save_mosaic` reporting progress of each stage,
+/// fails without writing the image when the save is cancelled
+pub fn save_mosaic_with_progress(
+    img: &ImgBuf<Rgba>, 
+    options: &MosaicOptions,
+    output: &OutputOptions,
+    path: &str,
+    progress: &dyn SaveProgress,
+) -> Result<(), String> {
+    let size = match output.kind {
+        OutputKind::Mosaic => 
+            save_composed(img, options, output, options.repeat, path, progress)?,
+        OutputKind::Texture => {
+            progress.progress(SaveStage::Compose, 0.0);
+            let texture = create_texture(img, options);
+            save_resampled(texture, options, output, path, progress)?
+        },
+        OutputKind::Cell => save_composed(img, options, output, (1, 1), path, progress)?,
+    };
+
+    match output.sidecar {
+        true => TilingMetadata::new(path, size, options, output).save(path),
+        false => Ok(()),
+    }
+}
+
+/// Composes mosaic from the patch with blended seams and saves it, 
+/// returns size of the saved image
+fn save_composed(
+    img: &ImgBuf<Rgba>, 
+    options: &MosaicOptions, 
+    output: &OutputOptions,
+    repeat: (usize, usize),
+    path: &str,
+    progress: &dyn SaveProgress,
+) -> Result<ImgSize, String> {
+    progress.progress(SaveStage::Compose, 0.0);
+    let blended = options.blend_patch(img);
+    let img = blended.as_ref().unwrap_or(img);
+    let pattern = options.create_pattern();
+    let cell = pattern.cell_size(img.size());
+    let native = ImgSize::new(cell.x*max(1, repeat.0), cell.y*max(1, repeat.1));
+
+    let sampler = MosaicSampler::new(img, pattern.as_ref(), (0.0, 0.0), 1.0);
+
+    if output.target_size(native) == native && supports_streaming(path) {
+        save_png_stream(&sampler, native, path, output.dpi, progress)?;
+        return Ok(native);
+    }
+
+    let done = AtomicUsize::new(0);
+
+    let row_done = || {
+        let rows = done.fetch_add(1, Ordering::Relaxed) + 1;
+
+        if rows % PROGRESS_ROWS == 0 {
+            progress.progress(SaveStage::Compose, rows as f64/native.y as f64);
+        }
+    };
+
+    let mosaic = render_rows(&sampler, native, &|| progress.cancelled(), &row_done);
+    check_cancelled(progress)?;
+    let mosaic = mosaic.ok_or("Mosaic composition failed")?;
+    save_resampled(mosaic, options, output, path, progress)
+}
+
+/// Resamples image to the output size and saves it, returns size of the saved image.
+/// All output kinds consist of whole unit cells or textures, so they are 
+/// resampled with kernels wrapping around borders and stay tileable.
+fn save_resampled(
+    image: ImgBuf<Rgba>, 
+    options: &MosaicOptions, 
+    output: &OutputOptions, 
+    path: &str,
+    progress: &dyn SaveProgress,
+) -> Result<ImgSize, String> {
+    check_cancelled(progress)?;
+    let size = output.target_size(image.size());
+
+    let image = match size == image.size() {
+        true => image,
+        false => resample_tileable(&image, size, options.filter),
+    };
+
+    progress.progress(SaveStage::Compose, 1.0);
+    check_cancelled(progress)?;
+    // Encoder cannot be interrupted, cancel is checked after it
+    progress.progress(SaveStage::Encode, 0.0);
+    let data = encode_image(image, path, output.dpi)?;
+    progress.progress(SaveStage::Encode, 1.0);
+    write_file(&data, path, progress)?;
+    Ok(size)
+}
+
+/// Composes unit cell of the pattern repeated in X and Y direction, 
+/// each output pixel is sampled directly from the source patch
+pub fn create_mosaic(
+    image: &ImgBuf<Rgba>, 
+    pattern: &dyn MosaicPattern,
+    repeat: (usize, usize),
+) -> ImgBuf<Rgba> {
+    let cell = pattern.cell_size(image.size());
+    let size = ImgSize::new(cell.x*max(1, repeat.0), cell.y*max(1, repeat.1));
+    render_mosaic_view(image, pattern, (0.0, 0.0), 1.0, size, &|| false)
+        .expect("Composition without cancellation always finishes")
+}
+
+/// Creates single seamlessly tileable texture from the patch
+pub fn create_texture(image: &ImgBuf<Rgba>, options: &MosaicOptions) -> ImgBuf<Rgba> {
+    offset_and_heal(image, options.texture_blend(), options.blend_width)
+}
+
+/// Renders part of the mosaic, `origin` is the mosaic point displayed in 
+/// the top left corner and `scale` is number of output pixels per mosaic pixel.
+/// Rendering stops and returns None as soon as `cancelled` returns true.
+pub fn render_mosaic_view(
+    image: &ImgBuf<Rgba>, 
+    pattern: &dyn MosaicPattern,
+    origin: (f64, f64),
+    scale: f64,
+    size: ImgSize,
+    cancelled: &(dyn Fn() -> bool + Sync),
+) -> Option<ImgBuf<Rgba>> {
+    let sampler = MosaicSampler::new(image, pattern, origin, scale);
+    render_rows(&sampler, size, cancelled, &|| {})
+}
+
+/// Renders all rows of the image, `row_done` is called after each finished row
+fn render_rows(
+    sampler: &MosaicSampler,
+    size: ImgSize,
+    cancelled: &(dyn Fn() -> bool + Sync),
+    row_done: &(dyn Fn() + Sync),
+) -> Option<ImgBuf<Rgba>> {
+    let mut pixels = vec![[0u8; 4]; size.x*size.y];
+
+    // Rows are computed independently from the source patch in parallel
+    pixels.par_chunks_mut(max(1, size.x)).enumerate().for_each(|(y, row)| {
+        if !cancelled() {
+            sampler.render_row(y, row);
+            row_done();
+        }
+    });
+
+    match cancelled() {
+        true => None,
+        false => Some(ImgBuf::from_vec(size, pixels)),
+    }
+}
+
+/// Samples mosaic pixels directly from the source patch
+pub(crate) struct MosaicSampler<'a> {
+    image: &'a ImgBuf<Rgba>,
+    mapper: PointMapper<'a>,
+    cell: (f64, f64),
+    interpolated: bool,
+    origin: (f64, f64),
+    scale: f64,
+}
+
+impl<'a> MosaicSampler<'a> {
+    pub fn new(
+        image: &'a ImgBuf<Rgba>, pattern: &'a dyn MosaicPattern, origin: (f64, f64), scale: f64
+    ) -> Self {
+        let patch = image.size();
+        let cell = pattern.cell_size(patch);
+
+        Self {
+            image, origin, scale,
+            mapper: pattern.point_mapper(patch),
+            cell: (cell.x as f64, cell.y as f64),
+            interpolated: pattern.interpolated(),
+        }
+    }
+
+    /// Fills one output row, row width is given by the slice length
+    pub fn render_row(&self, y: usize, row: &mut [Rgba]) {
+        let cell_y = (self.origin.1 + (y as f64 + 0.5)/self.scale).rem_euclid(self.cell.1);
+
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let cell_x = (self.origin.0 + (x as f64 + 0.5)/self.scale).rem_euclid(self.cell.0);
+            let (px, py) = (self.mapper)(cell_x, cell_y);
+
+            *pixel = match self.interpolated {
+                true => sample_bilinear(self.image, px, py),
+                false => sample(self.image, px, py),
+            };
+        }
+    }
+}
+
+/// Returns the patch pixel containing the given point, points outside
+/// of the patch are clamped to its border
+fn sample(image: &ImgBuf<Rgba>, x: f64, y: f64) -> Rgba {
+    let x = clamp_index(x, image.width());
+    let y = clamp_index(y, image.height());
+    image.line_ref(y)[x]
+}
+
+/// Interpolates the four patch pixels nearest to the given point
+fn sample_bilinear(image: &ImgBuf<Rgba>, x: f64, y: f64) -> Rgba {
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (fx, fy) = (x - x.floor(), y - y.floor());
+    let (w, h) = (image.width(), image.height());
+    let (x1, y1) = (clamp_index(x, w), clamp_index(y, h));
+    let (x2, y2) = (clamp_index(x + 1.0, w), clamp_index(y + 1.0, h));
+    let top = image.line_ref(y1);
+    let bottom = image.line_ref(y2);
+    let mut result = [0u8; 4];
+
+    for channel in 0..4 {
+        let value = 
+            top[x1][channel] as f64*(1.0 - fx)*(1.0 - fy) +
+            top[x2][channel] as f64*fx*(1.0 - fy) + 
+            bottom[x1][channel] as f64*(1.0 - fx)*fy + 
+            bottom[x2][channel] as f64*fx*fy;
+        result[channel] = value.round() as u8;
+    }
+
+    result
+}
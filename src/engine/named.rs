@@ -0,0 +1,65 @@
+//! Enumerations selectable by name on the command line and in user interface
+
+/// Value with a short name used on the command line and in project files
+/// and a human readable title displayed in user interface
+pub trait Named: Copy + 'static {
+    /// All values in the order displayed in user interface
+    fn all() -> Vec<Self>;
+
+    /// Short name used on the command line
+    fn name(self) -> &'static str;
+
+    /// Human readable name displayed in user interface
+    fn title(self) -> &'static str;
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|value| value.name() == name)
+    }
+}
+
+/// Converts named type from and into its short name, so that it is
+/// serialized by name, `$what` describes the type in error messages
+macro_rules! name_conversions {
+    ($type:ident, $what:expr) => {
+        impl From<$type> for String {
+            fn from(value: $type) -> String {
+                $crate::Named::name(value).to_owned()
+            }
+        }
+
+        impl std::convert::TryFrom<String> for $type {
+            type Error = String;
+
+            fn try_from(name: String) -> Result<$type, String> {
+                <$type as $crate::Named>::from_name(&name)
+                    .ok_or(format!("Unknown {} {}", $what, name))
+            }
+        }
+    };
+}
+
+/// Implements `Named` for enumeration of unit variants listed 
+/// with their names and titles
+macro_rules! named_enum {
+    ($type:ident, $what:expr, [$($variant:ident => ($name:expr, $title:expr)),* $(,)?]) => {
+        impl $crate::Named for $type {
+            fn all() -> Vec<$type> {
+                vec![$($type::$variant),*]
+            }
+
+            fn name(self) -> &'static str {
+                match self {
+                    $($type::$variant => $name),*
+                }
+            }
+
+            fn title(self) -> &'static str {
+                match self {
+                    $($type::$variant => $title),*
+                }
+            }
+        }
+
+        name_conversions!($type, $what);
+    };
+}
@@ -0,0 +1,88 @@
+use nanocv::{ImgBuf, ImgSize};
+use serde::{Serialize, Deserialize};
+use std::cmp::max;
+use super::pattern::{
+    MosaicPattern, PatternId, Mirror, MirrorX, MirrorY, Rotation, Translation, PointReflection
+};
+use super::kaleidoscope::Kaleidoscope;
+use super::resample::Filter;
+use super::blend::{SeamBlend, heal_seams};
+use super::image::Rgba;
+
+/// Settings of the mosaic shared by preview, export and command line
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MosaicOptions {
+    pub pattern: PatternId,
+    /// Number of segments of the kaleidoscope pattern
+    pub segments: u32,
+    /// Number of unit cells repeated in X and Y direction
+    pub repeat: (usize, usize),
+    /// Filter used to scale previews and exported images
+    pub filter: Filter,
+    /// Blending of patch borders in translation tiling
+    pub blend: SeamBlend,
+    /// Width of cross-faded patch borders in pixels
+    pub blend_width: usize,
+}
+
+impl Default for MosaicOptions {
+    fn default() -> Self {
+        Self {
+            pattern: PatternId::Mirror,
+            segments: 8,
+            repeat: (1, 1),
+            filter: Filter::Bilinear,
+            blend: SeamBlend::None,
+            blend_width: 32,
+        }
+    }
+}
+
+impl MosaicOptions {
+    pub fn create_pattern(&self) -> Box<dyn MosaicPattern> {
+        match self.pattern {
+            PatternId::Mirror => Box::new(Mirror),
+            PatternId::MirrorX => Box::new(MirrorX),
+            PatternId::MirrorY => Box::new(MirrorY),
+            PatternId::Rotation => Box::new(Rotation),
+            PatternId::Translation => Box::new(Translation),
+            PatternId::PointReflection => Box::new(PointReflection),
+            PatternId::Kaleidoscope => Box::new(Kaleidoscope::new(self.segments)),
+            PatternId::Wallpaper(group) => Box::new(group),
+        }
+    }
+
+    /// Seam blending is used by translation tiling only
+    pub fn seam_blend(&self) -> SeamBlend {
+        match self.pattern {
+            PatternId::Translation => self.blend,
+            _ => SeamBlend::None,
+        }
+    }
+
+    /// Blends patch borders before composition, None if blending is not used
+    pub fn blend_patch(&self, patch: &ImgBuf<Rgba>) -> Option<ImgBuf<Rgba>> {
+        heal_seams(patch, self.seam_blend(), self.blend_width)
+    }
+
+    /// Seam healing of the texture output, textures are always healed
+    pub fn texture_blend(&self) -> SeamBlend {
+        match self.blend {
+            SeamBlend::None => SeamBlend::Gradient,
+            blend => blend,
+        }
+    }
+
+    /// Size of the pattern unit cell composed from a patch of given size
+    pub fn cell_size(&self, patch: ImgSize) -> ImgSize {
+        let patch = self.seam_blend().blended_size(patch, self.blend_width);
+        self.create_pattern().cell_size(patch)
+    }
+
+    /// Size of the whole mosaic composed from a patch of given size
+    pub fn output_size(&self, patch: ImgSize) -> ImgSize {
+        let cell = self.cell_size(patch);
+        ImgSize::new(cell.x*max(1, self.repeat.0), cell.y*max(1, self.repeat.1))
+    }
+}
@@ -0,0 +1,91 @@
+use nanocv::ImgSize;
+use serde::{Serialize, Deserialize};
+use std::cmp::max;
+use super::options::MosaicOptions;
+
+pub const MM_PER_INCH: f64 = 25.4;
+/// Resolution offered for saved images until other is chosen
+pub const DEFAULT_DPI: f64 = 300.0;
+
+/// Kind of the saved image, stored in project files by its short name
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum OutputKind {
+    /// Unit cells of the pattern repeated in X and Y direction
+    Mosaic,
+    /// Single seamlessly tileable patch, shifted by half of its size 
+    /// with healed seams
+    Texture,
+    /// Single unit cell of the pattern
+    Cell,
+}
+
+named_enum!(OutputKind, "output kind", [
+    Mosaic => ("mosaic", "Mosaic"),
+    Texture => ("texture", "Seamless texture"),
+    Cell => ("cell", "Unit cell"),
+]);
+
+impl OutputKind {
+    /// Native size of the saved image created from a patch of given size
+    pub fn native_size(self, options: &MosaicOptions, patch: ImgSize) -> ImgSize {
+        match self {
+            OutputKind::Mosaic => options.output_size(patch),
+            OutputKind::Texture => 
+                options.texture_blend().blended_size(patch, options.blend_width),
+            OutputKind::Cell => options.cell_size(patch),
+        }
+    }
+}
+
+impl Default for OutputKind {
+    fn default() -> Self {
+        OutputKind::Mosaic
+    }
+}
+
+/// Resolution of the saved image, mosaic is saved in its native
+/// resolution when no size is given
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputOptions {
+    pub kind: OutputKind,
+    /// Width in pixels, derived from height and mosaic aspect ratio if missing
+    pub width: Option<usize>,
+    /// Height in pixels, derived from width and mosaic aspect ratio if missing
+    pub height: Option<usize>,
+    /// Dots per inch written into image metadata
+    pub dpi: Option<f64>,
+    /// Tiling metadata are saved into .json file next to the image
+    pub sidecar: bool,
+}
+
+impl OutputOptions {
+    /// Creates options for printing a physical size given in millimeters
+    pub fn from_physical(width_mm: Option<f64>, height_mm: Option<f64>, dpi: f64) -> Self {
+        let to_pixels = |mm: f64| max(1, (mm/MM_PER_INCH*dpi).round() as usize);
+
+        Self {
+            width: width_mm.map(to_pixels),
+            height: height_mm.map(to_pixels),
+            dpi: Some(dpi),
+            ..Self::default()
+        }
+    }
+
+    /// Size of the saved image for a mosaic of given native size
+    pub fn target_size(&self, mosaic: ImgSize) -> ImgSize {
+        let aspect = mosaic.x as f64/max(1, mosaic.y) as f64;
+
+        match (self.width, self.height) {
+            (Some(width), Some(height)) => ImgSize::new(max(1, width), max(1, height)),
+            (Some(width), None) => ImgSize::new(
+                max(1, width), max(1, (width as f64/aspect).round() as usize)
+            ),
+            (None, Some(height)) => ImgSize::new(
+                max(1, (height as f64*aspect).round() as usize), max(1, height)
+            ),
+            (None, None) => mosaic,
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use nanocv::{ImgBuf, ImgSize, Img, Range2d, filter::map_range};
+use super::image::Rgba;
+
+/// Copies selected range of the image into a new buffer, returns
+/// 1x1 transparent image if the range is empty
+pub fn select_patch(image: &ImgBuf<Rgba>, range: Range2d<isize>) -> ImgBuf<Rgba> {
+    if range.width() > 0 && range.height() > 0 {
+        let patch_size = ImgSize::new(range.width() as usize, range.height() as usize);
+        let mut buffer = ImgBuf::<Rgba>::new(patch_size);
+        let output_range = buffer.range();
+        map_range(image, &mut buffer, range, output_range, |i, _| i);    
+        buffer
+    } else {
+        ImgBuf::<Rgba>::new_init(ImgSize::new(1, 1), [0, 0, 0, 0])
+    }        
+}
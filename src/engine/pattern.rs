@@ -0,0 +1,203 @@
+use nanocv::ImgSize;
+use serde::{Serialize, Deserialize};
+use std::cmp::min;
+use super::{wallpaper::WallpaperGroup, named::Named};
+
+/// Symmetry pattern composing a repeating unit cell from image patch,
+/// patterns are shared by threads composing mosaic rows in parallel
+pub trait MosaicPattern: Sync {
+    /// Size of the unit cell composed from a patch of given size
+    fn cell_size(&self, patch: ImgSize) -> ImgSize;
+
+    /// Maps a point within the unit cell into patch coordinates
+    fn map_point(&self, patch: ImgSize, x: f64, y: f64) -> (f64, f64);
+
+    /// Point mapping for a patch of given size used for every composed pixel,
+    /// patterns override it to compute values shared by all points only once
+    fn point_mapper<'a>(&'a self, patch: ImgSize) -> PointMapper<'a> {
+        Box::new(move |x, y| self.map_point(patch, x, y))
+    }
+
+    /// Whether patch should be sampled with bilinear interpolation,
+    /// patterns not aligned with pixel grid look smoother that way
+    fn interpolated(&self) -> bool {
+        false
+    }
+}
+
+/// Maps a point within the unit cell into patch coordinates
+pub type PointMapper<'a> = Box<dyn Fn(f64, f64) -> (f64, f64) + Sync + 'a>;
+
+/// Built-in patterns selectable in user interface and command line,
+/// stored in project files by their short name
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum PatternId {
+    Mirror,
+    MirrorX,
+    MirrorY,
+    Rotation,
+    Translation,
+    PointReflection,
+    Kaleidoscope,
+    Wallpaper(WallpaperGroup),
+}
+
+impl Named for PatternId {
+    fn all() -> Vec<PatternId> {
+        let mut patterns = vec![
+            PatternId::Mirror,
+            PatternId::MirrorX,
+            PatternId::MirrorY,
+            PatternId::Rotation,
+            PatternId::Translation,
+            PatternId::PointReflection,
+            PatternId::Kaleidoscope,
+        ];
+
+        patterns.extend(WallpaperGroup::all().into_iter().map(PatternId::Wallpaper));
+        patterns
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            PatternId::Mirror => "mirror",
+            PatternId::MirrorX => "mirror-x",
+            PatternId::MirrorY => "mirror-y",
+            PatternId::Rotation => "rotation",
+            PatternId::Translation => "translation",
+            PatternId::PointReflection => "point-reflection",
+            PatternId::Kaleidoscope => "kaleidoscope",
+            PatternId::Wallpaper(group) => group.name(),
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            PatternId::Mirror => "Mirror X and Y",
+            PatternId::MirrorX => "Mirror X",
+            PatternId::MirrorY => "Mirror Y",
+            PatternId::Rotation => "Rotation 90°",
+            PatternId::Translation => "Translation",
+            PatternId::PointReflection => "Point reflection",
+            PatternId::Kaleidoscope => "Kaleidoscope",
+            PatternId::Wallpaper(group) => group.title(),
+        }
+    }
+}
+
+name_conversions!(PatternId, "pattern");
+
+/// Original patch with its horizontal, vertical and diagonal mirrors
+pub struct Mirror;
+
+/// Original patch next to its horizontal mirror
+pub struct MirrorX;
+
+/// Original patch above its vertical mirror
+pub struct MirrorY;
+
+/// Square patch rotated by 90° around the center of the cell
+pub struct Rotation;
+
+/// Patch repeated without any transformation
+pub struct Translation;
+
+/// Patch alternating with its copy rotated by 180° in a checkerboard
+pub struct PointReflection;
+
+impl MosaicPattern for Mirror {
+    fn cell_size(&self, patch: ImgSize) -> ImgSize {
+        patch*2
+    }
+
+    fn map_point(&self, patch: ImgSize, x: f64, y: f64) -> (f64, f64) {
+        (fold(x, patch.x as f64), fold(y, patch.y as f64))
+    }
+}
+
+impl MosaicPattern for MirrorX {
+    fn cell_size(&self, patch: ImgSize) -> ImgSize {
+        ImgSize::new(patch.x*2, patch.y)
+    }
+
+    fn map_point(&self, patch: ImgSize, x: f64, y: f64) -> (f64, f64) {
+        (fold(x, patch.x as f64), y)
+    }
+}
+
+impl MosaicPattern for MirrorY {
+    fn cell_size(&self, patch: ImgSize) -> ImgSize {
+        ImgSize::new(patch.x, patch.y*2)
+    }
+
+    fn map_point(&self, patch: ImgSize, x: f64, y: f64) -> (f64, f64) {
+        (x, fold(y, patch.y as f64))
+    }
+}
+
+impl MosaicPattern for Rotation {
+    fn cell_size(&self, patch: ImgSize) -> ImgSize {
+        let side = min(patch.x, patch.y);
+        ImgSize::new(side*2, side*2)
+    }
+
+    fn map_point(&self, patch: ImgSize, x: f64, y: f64) -> (f64, f64) {
+        let (side, offset) = central_square(patch);
+        let (mut x, mut y) = (x, y);
+
+        // Rotate counter-clockwise around the cell center until
+        // the point lands in the top left quadrant
+        for _ in 0..3 {
+            if x < side && y < side { break; }
+            let rotated = (y, 2.0*side - x);
+            x = rotated.0;
+            y = rotated.1;
+        }
+
+        (x + offset.0, y + offset.1)
+    }
+}
+
+impl MosaicPattern for Translation {
+    fn cell_size(&self, patch: ImgSize) -> ImgSize {
+        patch
+    }
+
+    fn map_point(&self, _patch: ImgSize, x: f64, y: f64) -> (f64, f64) {
+        (x, y)
+    }
+}
+
+impl MosaicPattern for PointReflection {
+    fn cell_size(&self, patch: ImgSize) -> ImgSize {
+        patch*2
+    }
+
+    fn map_point(&self, patch: ImgSize, x: f64, y: f64) -> (f64, f64) {
+        let (w, h) = (patch.x as f64, patch.y as f64);
+        let rotated = (x >= w) != (y >= h);
+        let (x, y) = (x % w, y % h);
+
+        match rotated {
+            true => (w - x, h - y),
+            false => (x, y),
+        }
+    }
+}
+
+/// Side and top left corner of the central square of a non-square patch
+pub(crate) fn central_square(patch: ImgSize) -> (f64, (f64, f64)) {
+    let side = min(patch.x, patch.y) as f64;
+    let offset_x = ((patch.x as f64 - side)/2.0).floor();
+    let offset_y = ((patch.y as f64 - side)/2.0).floor();
+    (side, (offset_x, offset_y))
+}
+
+/// Maps coordinate from range 0..2*size into 0..size, mirroring the upper half
+pub(crate) fn fold(value: f64, size: f64) -> f64 {
+    match value < size {
+        true => value,
+        false => 2.0*size - value,
+    }
+}
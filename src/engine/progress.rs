@@ -0,0 +1,50 @@
+/// Stage of saving an image
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SaveStage {
+    /// Output pixels are composed from the patch
+    Compose,
+    /// Image is encoded into file format
+    Encode,
+    /// Encoded data are written into file
+    Write,
+}
+
+impl SaveStage {
+    /// Human readable name displayed in user interface
+    pub fn title(self) -> &'static str {
+        match self {
+            SaveStage::Compose => "Composing",
+            SaveStage::Encode => "Encoding",
+            SaveStage::Write => "Writing",
+        }
+    }
+}
+
+/// Receives progress of a save running on multiple threads
+/// and allows to cancel it
+pub trait SaveProgress: Sync {
+    /// Called with finished fraction of the stage from 0.0 to 1.0
+    fn progress(&self, stage: SaveStage, done: f64);
+
+    /// Save is stopped with an error as soon as possible when true
+    fn cancelled(&self) -> bool;
+}
+
+/// Ignores progress, save is never cancelled
+pub struct NoProgress;
+
+impl SaveProgress for NoProgress {
+    fn progress(&self, _stage: SaveStage, _done: f64) {}
+
+    fn cancelled(&self) -> bool {
+        false
+    }
+}
+
+/// Fails if the save has been cancelled
+pub(crate) fn check_cancelled(progress: &dyn SaveProgress) -> Result<(), String> {
+    match progress.cancelled() {
+        true => Err(format!("Save cancelled")),
+        false => Ok(()),
+    }
+}
@@ -0,0 +1,73 @@
+use serde::{Serialize, Deserialize};
+use std::{env, fs, path::{Path, PathBuf}};
+use super::{options::MosaicOptions, output::OutputOptions, utils::convert_err};
+
+/// File name suffix of project files
+pub const PROJECT_SUFFIX: &str = "nanomosaic";
+
+/// Project file storing everything needed to recreate a mosaic
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Project {
+    /// Path to the source image, relative paths are resolved
+    /// against the directory containing the project file
+    pub source: String,
+    /// First corner of the selection in source image pixels
+    pub start: (isize, isize),
+    /// Second corner of the selection in source image pixels
+    pub end: (isize, isize),
+    #[serde(default)]
+    pub mosaic: MosaicOptions,
+    #[serde(default)]
+    pub output: OutputOptions,
+}
+
+impl Project {
+    /// Selected area as x, y, width and height
+    pub fn rect(&self) -> (isize, isize, isize, isize) {
+        let x = self.start.0.min(self.end.0);
+        let y = self.start.1.min(self.end.1);
+        (x, y, (self.end.0 - self.start.0).abs(), (self.end.1 - self.start.1).abs())
+    }
+
+    pub fn load(path: &str) -> Result<Project, String> {
+        let data = convert_err(fs::read_to_string(path))?;
+        let mut project: Project = convert_err(serde_json::from_str(&data))?;
+
+        let source = Path::new(&project.source);
+
+        if source.is_relative() {
+            if let Some(directory) = Path::new(path).parent() {
+                project.source = directory.join(source).to_string_lossy().into_owned();
+            }
+        }
+
+        Ok(project)
+    }
+
+    /// Saves project, source path is stored relative to the project file 
+    /// directory if the image is located in it, as absolute path otherwise
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let project = Project {source: stored_source(&self.source, path), ..self.clone()};
+        let data = convert_err(serde_json::to_string_pretty(&project))?;
+        convert_err(fs::write(path, data))
+    }
+}
+
+/// Source path as stored in the project file, relative source paths
+/// given by the user are relative to the working directory
+fn stored_source(source: &str, project: &str) -> String {
+    // Missing image is still stored with absolute path
+    let source = fs::canonicalize(source)
+        .or_else(|_| env::current_dir().map(|directory| directory.join(source)))
+        .unwrap_or_else(|_| PathBuf::from(source));
+
+    let directory = match Path::new(project).parent() {
+        Some(directory) if directory != Path::new("") => fs::canonicalize(directory),
+        _ => fs::canonicalize("."),
+    };
+
+    let relative = directory.ok()
+        .and_then(|directory| source.strip_prefix(directory).ok().map(PathBuf::from));
+
+    relative.unwrap_or(source).to_string_lossy().into_owned()
+}
@@ -0,0 +1,166 @@
+//! Search for a selection producing the least visible seams in the mosaic
+
+use nanocv::{ImgBuf, ImgSize, Img, Range2d};
+use std::cmp::{min, max};
+use super::{image::Rgba, patch::select_patch, pattern::MosaicPattern};
+use super::resample::{resample, Filter};
+use super::utils::clamp_index;
+
+/// Longer side of the selection in pixels of the reduced image used for search
+const SEARCH_RESOLUTION: f64 = 96.0;
+
+/// Searched neighbourhood relative to the selection size
+const SEARCH_RADIUS: f64 = 0.1;
+
+/// Pair of patch pixels displayed next to each other in the mosaic
+type SeamPair = ((usize, usize), (usize, usize));
+
+/// Searches neighbourhood of the selection given as x, y, width and height
+/// for a rectangle whose patch has the smallest colour difference across 
+/// the pattern seams, size is kept unless `resize` is set
+pub fn refine_selection(
+    image: &ImgBuf<Rgba>,
+    pattern: &dyn MosaicPattern,
+    rect: (isize, isize, isize, isize),
+    resize: bool,
+) -> (isize, isize, isize, isize) {
+    let (x, y, width, height) = rect;
+
+    if width < 2 || height < 2 {
+        return rect;
+    }
+
+    // Search is done in reduced resolution around the selection
+    let scale = (SEARCH_RESOLUTION/max(width, height) as f64).min(1.0);
+    let radius = max(1, (max(width, height) as f64*scale*SEARCH_RADIUS).round() as isize);
+    let margin = ((radius + 1) as f64/scale).ceil() as isize;
+    let (left, top) = (max(0, x - margin), max(0, y - margin));
+    let right = min(image.width() as isize, x + width + margin);
+    let bottom = min(image.height() as isize, y + height + margin);
+    let region = select_patch(image, Range2d::new(left..right, top..bottom));
+
+    let reduced_size = ImgSize::new(
+        max(1, (region.width() as f64*scale).round() as usize),
+        max(1, (region.height() as f64*scale).round() as usize),
+    );
+    let reduced = resample(&region, reduced_size, Filter::Bilinear);
+    let scale_x = reduced.width() as f64/region.width() as f64;
+    let scale_y = reduced.height() as f64/region.height() as f64;
+
+    let current = (
+        ((x - left) as f64*scale_x).round() as isize,
+        ((y - top) as f64*scale_y).round() as isize,
+        max(2, (width as f64*scale_x).round() as isize),
+        max(2, (height as f64*scale_y).round() as isize),
+    );
+
+    let size_radius = if resize { max(1, radius/2) } else { 0 };
+    let mut best = (std::f64::MAX, current);
+
+    for dh in -size_radius..=size_radius {
+        for dw in -size_radius..=size_radius {
+            let (w, h) = (current.2 + dw, current.3 + dh);
+
+            if w < 2 || h < 2 {
+                continue;
+            }
+
+            let pairs = seam_pairs(pattern, ImgSize::new(w as usize, h as usize));
+
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let (px, py) = (current.0 + dx, current.1 + dy);
+                    let inside = px >= 0 && py >= 0 
+                        && px + w <= reduced.width() as isize 
+                        && py + h <= reduced.height() as isize;
+
+                    if !inside {
+                        continue;
+                    }
+
+                    let cost = seam_cost(&reduced, &pairs, px as usize, py as usize);
+
+                    // Current selection is kept unless a better one is found
+                    let is_current = (px, py, w, h) == current;
+
+                    if cost < best.0 || (is_current && cost <= best.0) {
+                        best = (cost, (px, py, w, h));
+                    }
+                }
+            }
+        }
+    }
+
+    let (px, py, w, h) = best.1;
+
+    if best.1 == current {
+        return rect;
+    }
+
+    let x = max(0, left + (px as f64/scale_x).round() as isize);
+    let y = max(0, top + (py as f64/scale_y).round() as isize);
+    let width = min(image.width() as isize - x, (w as f64/scale_x).round() as isize);
+    let height = min(image.height() as isize - y, (h as f64/scale_y).round() as isize);
+    (x, y, width, height)
+}
+
+/// Finds patch pixels that are neighbours in the mosaic but not in the patch,
+/// colour differences between them make the seams visible
+fn seam_pairs(pattern: &dyn MosaicPattern, patch: ImgSize) -> Vec<SeamPair> {
+    let cell = pattern.cell_size(patch);
+    let mapper = pattern.point_mapper(patch);
+    let mut mapped = Vec::with_capacity(cell.x*cell.y);
+
+    for y in 0..cell.y {
+        for x in 0..cell.x {
+            let (px, py) = mapper(x as f64 + 0.5, y as f64 + 0.5);
+            mapped.push((clamp_index(px, patch.x), clamp_index(py, patch.y)));
+        }
+    }
+
+    let mut pairs = Vec::new();
+
+    for y in 0..cell.y {
+        for x in 0..cell.x {
+            let a = mapped[y*cell.x + x];
+            
+            // Cells are repeated, so seams wrap around cell borders
+            for &(nx, ny) in &[((x + 1) % cell.x, y), (x, (y + 1) % cell.y)] {
+                let b = mapped[ny*cell.x + nx];
+                let distance = max(distance(a.0, b.0), distance(a.1, b.1));
+
+                if distance > 1 {
+                    pairs.push((a, b));
+                }
+            }
+        }
+    }
+
+    pairs
+}
+
+/// Mean squared colour difference across the seams of patch at the given position
+fn seam_cost(image: &ImgBuf<Rgba>, pairs: &[SeamPair], x: usize, y: usize) -> f64 {
+    if pairs.is_empty() {
+        return 0.0;
+    }
+
+    let mut sum = 0.0;
+
+    for &((ax, ay), (bx, by)) in pairs {
+        let a = image.line_ref(y + ay)[x + ax];
+        let b = image.line_ref(y + by)[x + bx];
+
+        for channel in 0..3 {
+            let difference = a[channel] as f64 - b[channel] as f64;
+            sum += difference*difference;
+        }
+    }
+
+    sum/pairs.len() as f64
+}
+
+fn distance(a: usize, b: usize) -> usize {
+    if a > b { a - b } else { b - a }
+}
+
@@ -0,0 +1,238 @@
+use nanocv::{ImgBuf, ImgSize, Img};
+use nanocv::filter::resize_nearest_new;
+use serde::{Serialize, Deserialize};
+use std::f64::consts::PI;
+use super::image::Rgba;
+
+/// Resampling filter used to scale images, stored in project files
+/// by its short name
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+    Bicubic,
+    Lanczos,
+}
+
+named_enum!(Filter, "filter", [
+    Nearest => ("nearest", "Nearest neighbour"),
+    Bilinear => ("bilinear", "Bilinear"),
+    Bicubic => ("bicubic", "Bicubic"),
+    Lanczos => ("lanczos", "Lanczos"),
+]);
+
+impl Filter {
+    /// Radius of the filter kernel in source pixels when upscaling
+    fn support(self) -> f64 {
+        match self {
+            Filter::Nearest => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Bicubic => 2.0,
+            Filter::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(self, x: f64) -> f64 {
+        let x = x.abs();
+
+        match self {
+            Filter::Nearest => if x < 0.5 { 1.0 } else { 0.0 },
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            Filter::Bicubic => cubic(x),
+            Filter::Lanczos => if x < 3.0 { sinc(x)*sinc(x/3.0) } else { 0.0 },
+        }
+    }
+}
+
+/// Catmull-Rom cubic spline
+fn cubic(x: f64) -> f64 {
+    let a = -0.5;
+
+    if x < 1.0 {
+        ((a + 2.0)*x - (a + 3.0))*x*x + 1.0
+    } else if x < 2.0 {
+        (((x - 5.0)*x + 8.0)*x - 4.0)*a
+    } else {
+        0.0
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI*x).sin()/(PI*x)
+    }
+}
+
+/// Scales image into exactly the target size using the given filter,
+/// the filter is widened when downscaling to avoid aliasing
+pub fn resample(source: &ImgBuf<Rgba>, target: ImgSize, filter: Filter) -> ImgBuf<Rgba> {
+    resample_edges(source, target, filter, false)
+}
+
+/// Scales tileable image so that the result tiles seamlessly as well,
+/// filter kernels wrap around image borders instead of clamping to them
+pub fn resample_tileable(source: &ImgBuf<Rgba>, target: ImgSize, filter: Filter) -> ImgBuf<Rgba> {
+    resample_edges(source, target, filter, true)
+}
+
+fn resample_edges(
+    source: &ImgBuf<Rgba>, target: ImgSize, filter: Filter, wrap: bool
+) -> ImgBuf<Rgba> {
+    if filter == Filter::Nearest || source.size() == target {
+        return resize_nearest_new(source, target);
+    }
+
+    let (sw, sh) = (source.width(), source.height());
+    let (tw, th) = (target.x, target.y);
+    let columns = weights(sw, tw, filter, wrap);
+    let rows = weights(sh, th, filter, wrap);
+
+    // Horizontal pass into floating point buffer of size tw x sh
+    let mut horizontal = vec![[0f32; 4]; tw*sh];
+
+    for y in 0..sh {
+        let line = source.line_ref(y);
+
+        for (x, kernel) in columns.iter().enumerate() {
+            let mut sum = [0f32; 4];
+
+            for &(index, weight) in kernel {
+                let pixel = line[index];
+
+                for channel in 0..4 {
+                    sum[channel] += pixel[channel] as f32*weight;
+                }
+            }
+
+            horizontal[y*tw + x] = sum;
+        }
+    }
+
+    // Vertical pass into the output image
+    let mut pixels = Vec::with_capacity(tw*th);
+
+    for kernel in rows.iter() {
+        for x in 0..tw {
+            let mut sum = [0f32; 4];
+
+            for &(index, weight) in kernel {
+                let pixel = horizontal[index*tw + x];
+
+                for channel in 0..4 {
+                    sum[channel] += pixel[channel]*weight;
+                }
+            }
+
+            pixels.push([to_u8(sum[0]), to_u8(sum[1]), to_u8(sum[2]), to_u8(sum[3])]);
+        }
+    }
+
+    ImgBuf::from_vec(target, pixels)
+}
+
+/// Computes normalized filter weights for each target pixel together
+/// with indices of source pixels they apply to, kernels are clamped 
+/// to source borders or wrap around them
+fn weights(source: usize, target: usize, filter: Filter, wrap: bool) -> Vec<Vec<(usize, f32)>> {
+    let scale = target as f64/source as f64;
+    let stretch = if scale < 1.0 { 1.0/scale } else { 1.0 };
+    let support = filter.support()*stretch;
+
+    (0..target).map(|index| {
+        let center = (index as f64 + 0.5)/scale;
+        let mut start = (center - support).floor() as isize;
+        let mut end = (center + support).ceil() as isize;
+
+        if !wrap {
+            start = start.max(0);
+            end = end.min(source as isize).max(start + 1);
+        }
+
+        let mut kernel: Vec<f64> = (start..end)
+            .map(|x| filter.weight((x as f64 + 0.5 - center)/stretch))
+            .collect();
+
+        let sum: f64 = kernel.iter().sum();
+
+        if sum.abs() > std::f64::EPSILON {
+            kernel.iter_mut().for_each(|weight| *weight /= sum);
+        } else {
+            kernel.iter_mut().for_each(|weight| *weight = 0.0);
+            kernel[0] = 1.0;
+        }
+
+        let indices = (start..end).map(|x| x.rem_euclid(source as isize) as usize);
+        indices.zip(kernel.into_iter().map(|weight| weight as f32)).collect()
+    }).collect()
+}
+
+fn to_u8(value: f32) -> u8 {
+    value.round().max(0.0).min(255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::named::Named;
+
+    /// Image with given size repeating a 12 x 10 pixel tile
+    fn tiled(size: ImgSize) -> ImgBuf<Rgba> {
+        let pixels = (0..size.y)
+            .flat_map(|y| (0..size.x).map(move |x| (x % 12, y % 10)))
+            .map(|(x, y)| [(x*20) as u8, (y*25) as u8, ((x*7 + y*13)*3 % 256) as u8, 255])
+            .collect();
+
+        ImgBuf::from_vec(size, pixels)
+    }
+
+    #[test]
+    fn weights_sum_to_one() {
+        for filter in Filter::all() {
+            for &(source, target) in [(10, 25), (25, 10), (7, 3), (3, 1)].iter() {
+                for &wrap in [false, true].iter() {
+                    for kernel in weights(source, target, filter, wrap) {
+                        let sum: f32 = kernel.iter().map(|&(_, weight)| weight).sum();
+                        let name = filter.name();
+                        assert!((sum - 1.0).abs() < 1e-5, "{} {} -> {}", name, source, target);
+                        assert!(kernel.iter().all(|&(index, _)| index < source));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn wrapped_kernels_reach_opposite_border() {
+        let first = &weights(10, 5, Filter::Bicubic, true)[0];
+        assert!(first.iter().any(|&(index, weight)| index == 9 && weight != 0.0));
+
+        let first = &weights(10, 5, Filter::Bicubic, false)[0];
+        assert!(first.iter().all(|&(index, _)| index < 5));
+    }
+
+    #[test]
+    fn tileable_output_matches_resampled_tiling() {
+        let tile = tiled(ImgSize::new(12, 10));
+        let plane = tiled(ImgSize::new(36, 30));
+
+        // Nearest neighbour scaling is done by the library, it does not blend pixels
+        for &filter in [Filter::Bilinear, Filter::Bicubic, Filter::Lanczos].iter() {
+            let result = resample_tileable(&tile, ImgSize::new(18, 15), filter);
+            // Kernels in the center of resampled tiling do not reach its borders
+            let expected = resample(&plane, ImgSize::new(54, 45), filter);
+
+            for y in 0..15 {
+                let line = result.line_ref(y);
+                let center = &expected.line_ref(y + 15)[18..36];
+
+                for (a, b) in line.iter().zip(center.iter()) {
+                    let close = (0..4).all(|c| (a[c] as i32 - b[c] as i32).abs() <= 1);
+                    assert!(close, "{} row {}: {:?} != {:?}", filter.name(), y, a, b);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use nanocv::ImgSize;
+use serde::{Serialize, Deserialize};
+use std::{fs, path::{Path, PathBuf}};
+use super::{options::MosaicOptions, output::{OutputOptions, OutputKind}};
+use super::{pattern::PatternId, utils::convert_err};
+
+/// Tiling metadata saved next to the image, so that the image can be
+/// repeated at runtime instead of storing a pre-tiled texture
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TilingMetadata {
+    /// File name of the image in the same directory
+    pub image: String,
+    pub kind: OutputKind,
+    pub pattern: PatternId,
+    /// Image size in pixels, the image tiles seamlessly when repeated 
+    /// by translation with this period in X and Y direction
+    pub width: usize,
+    pub height: usize,
+    /// Number of pattern unit cells contained in the image
+    pub cells: (usize, usize),
+    pub dpi: Option<f64>,
+}
+
+impl TilingMetadata {
+    pub fn new(path: &str, size: ImgSize, options: &MosaicOptions, output: &OutputOptions) -> Self {
+        let image = Path::new(path).file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let cells = match output.kind {
+            OutputKind::Mosaic => (options.repeat.0.max(1), options.repeat.1.max(1)),
+            _ => (1, 1),
+        };
+
+        // Texture is a single patch made tileable, it repeats only by translation
+        let pattern = match output.kind {
+            OutputKind::Texture => PatternId::Translation,
+            _ => options.pattern,
+        };
+
+        Self {
+            image, 
+            kind: output.kind, 
+            pattern, 
+            width: size.x, 
+            height: size.y, 
+            cells, 
+            dpi: output.dpi,
+        }
+    }
+
+    /// Metadata file stored next to the image, with .json suffix
+    pub fn path(image_path: &str) -> PathBuf {
+        Path::new(image_path).with_extension("json")
+    }
+
+    pub fn save(&self, image_path: &str) -> Result<(), String> {
+        let data = convert_err(serde_json::to_string_pretty(self))?;
+        convert_err(fs::write(Self::path(image_path), data))
+    }
+}
@@ -0,0 +1,212 @@
+//! Streaming export encoding the mosaic row by row, memory use is
+//! proportional to the patch and output width, not to the output size
+
+use nanocv::ImgSize;
+use rayon::prelude::*;
+use flate2::{write::ZlibEncoder, Compression};
+use std::{fs::{self, File}, io::{self, BufWriter, Write}, path::Path};
+use super::{image::rgba_as_bytes, mosaic::MosaicSampler, density::png_density_data};
+use super::progress::{SaveProgress, SaveStage, check_cancelled};
+use super::utils::convert_err;
+
+/// Number of rows composed in parallel before they are encoded
+const BAND_HEIGHT: usize = 64;
+
+/// Only PNG files are written as a stream
+pub fn supports_streaming(path: &str) -> bool {
+    Path::new(path).extension()
+        .map(|suffix| suffix.to_string_lossy().to_lowercase() == "png")
+        .unwrap_or(false)
+}
+
+/// Composes mosaic of given size band by band and encodes it into PNG file,
+/// partially written file is removed when the save is cancelled
+pub fn save_png_stream(
+    sampler: &MosaicSampler, 
+    size: ImgSize, 
+    path: &str, 
+    dpi: Option<f64>,
+    progress: &dyn SaveProgress,
+) -> Result<(), String> {
+    let result = write_png_stream(sampler, size, path, dpi, progress);
+
+    if result.is_err() {
+        let _ = fs::remove_file(path);
+    }
+
+    result
+}
+
+fn write_png_stream(
+    sampler: &MosaicSampler, 
+    size: ImgSize, 
+    path: &str, 
+    dpi: Option<f64>,
+    progress: &dyn SaveProgress,
+) -> Result<(), String> {
+    let mut output = CheckedWriter::new(BufWriter::new(convert_err(File::create(path))?));
+    encode_png_stream(sampler, size, &mut output, dpi, progress)?;
+
+    let mut file = convert_err(output.finish())?;
+    convert_err(file.flush())?;
+    convert_err(file.get_ref().sync_all())?;
+    progress.progress(SaveStage::Write, 1.0);
+    Ok(())
+}
+
+/// Encodes PNG image with rows compressed band by band, each band is written
+/// as a separate IDAT chunk, so the compressed data are never held in memory
+fn encode_png_stream<W: Write>(
+    sampler: &MosaicSampler, 
+    size: ImgSize, 
+    output: W, 
+    dpi: Option<f64>,
+    progress: &dyn SaveProgress,
+) -> Result<(), String> {
+    let mut encoder = png::Encoder::new(output, size.x as u32, size.y as u32);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = convert_err(encoder.write_header())?;
+
+    if let Some(dpi) = dpi {
+        convert_err(writer.write_chunk(*b"pHYs", &png_density_data(dpi)))?;
+    }
+
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    let mut band = vec![[0u8; 4]; size.x*BAND_HEIGHT];
+
+    for top in (0..size.y).step_by(BAND_HEIGHT) {
+        check_cancelled(progress)?;
+        progress.progress(SaveStage::Encode, top as f64/size.y as f64);
+        let rows = BAND_HEIGHT.min(size.y - top);
+        let band = &mut band[..size.x*rows];
+
+        band.par_chunks_mut(size.x).enumerate().for_each(|(y, row)| {
+            sampler.render_row(top + y, row);
+        });
+
+        for row in band.chunks(size.x) {
+            // Each scanline starts with its filter type, rows are not filtered
+            convert_err(zlib.write_all(&[0]))?;
+            convert_err(zlib.write_all(rgba_as_bytes(row)))?;
+        }
+
+        let compressed = zlib.get_mut();
+
+        if !compressed.is_empty() {
+            convert_err(writer.write_chunk(*b"IDAT", compressed))?;
+            compressed.clear();
+        }
+    }
+
+    let compressed = convert_err(zlib.finish())?;
+    convert_err(writer.write_chunk(*b"IDAT", &compressed))?;
+    // IEND chunk is written when the writer is dropped, errors are kept by the output
+    drop(writer);
+    Ok(())
+}
+
+/// Keeps the first write error, PNG writer writes the final chunk when dropped
+/// and ignores its failure
+struct CheckedWriter<W: Write> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> CheckedWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, error: None }
+    }
+
+    /// Returns the inner writer or the first error that occurred
+    fn finish(self) -> io::Result<W> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.inner),
+        }
+    }
+
+    fn check<T>(&mut self, result: io::Result<T>) -> io::Result<T> {
+        if let Err(error) = &result {
+            if self.error.is_none() && error.kind() != io::ErrorKind::Interrupted {
+                self.error = Some(io::Error::new(error.kind(), error.to_string()));
+            }
+        }
+
+        result
+    }
+}
+
+impl<W: Write> Write for CheckedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.inner.write(buf);
+        self.check(result)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.inner.flush();
+        self.check(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nanocv::{Img, ImgBuf};
+    use super::super::{image::Rgba, io::load_image, mosaic::create_mosaic};
+    use super::super::{pattern::Mirror, progress::NoProgress};
+
+    /// Patch with distinct pixels, so any misplaced pixel is detected
+    fn patch() -> ImgBuf<Rgba> {
+        let size = ImgSize::new(40, 37);
+        let pixels = (0..size.x*size.y)
+            .map(|i| [(i % size.x*6) as u8, (i/size.x*6) as u8, (i % 251) as u8, 255])
+            .collect();
+        ImgBuf::from_vec(size, pixels)
+    }
+
+    fn assert_same_pixels(expected: &ImgBuf<Rgba>, actual: &[u8]) {
+        let stride = expected.width()*4;
+        assert_eq!(actual.len(), stride*expected.height());
+
+        for y in 0..expected.height() {
+            assert_eq!(rgba_as_bytes(expected.line_ref(y)), &actual[y*stride..(y + 1)*stride]);
+        }
+    }
+
+    #[test]
+    fn streamed_png_decodes_to_composed_mosaic() {
+        let patch = patch();
+        let expected = create_mosaic(&patch, &Mirror, (2, 1));
+        let sampler = MosaicSampler::new(&patch, &Mirror, (0.0, 0.0), 1.0);
+        let mut data = Vec::new();
+        encode_png_stream(&sampler, expected.size(), &mut data, Some(300.0), &NoProgress)
+            .unwrap();
+
+        let (info, mut reader) = png::Decoder::new(&data[..]).read_info().unwrap();
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (160, 74));
+        assert_same_pixels(&expected, &pixels);
+    }
+
+    #[test]
+    fn streamed_png_file_loads_as_composed_mosaic() {
+        let patch = patch();
+        let expected = create_mosaic(&patch, &Mirror, (1, 2));
+        let sampler = MosaicSampler::new(&patch, &Mirror, (0.0, 0.0), 1.0);
+        let path = std::env::temp_dir()
+            .join(format!("nanomosaic-stream-{}.png", std::process::id()));
+        let path = path.to_string_lossy();
+
+        save_png_stream(&sampler, expected.size(), &path, None, &NoProgress).unwrap();
+        let loaded = load_image(&path);
+        let _ = fs::remove_file(&*path);
+
+        let loaded = loaded.unwrap();
+        let pixels: Vec<Rgba> = (0..loaded.height())
+            .flat_map(|y| loaded.line_ref(y).to_vec())
+            .collect();
+        assert_same_pixels(&expected, rgba_as_bytes(&pixels));
+    }
+}
@@ -0,0 +1,14 @@
+use std::{cmp::min, fmt::Debug};
+
+pub(crate) fn convert_err<T, E: Debug>(input: Result<T, E>) -> Result<T, String> {
+    match input {
+        Ok(x) => Ok(x),
+        Err(err) => Err(format!("{:?}", err))
+    }
+}
+
+/// Index of the pixel containing the coordinate, coordinates outside 
+/// of the image are clamped to its border
+pub(crate) fn clamp_index(value: f64, size: usize) -> usize {
+    min(value.max(0.0) as usize, size - 1)
+}
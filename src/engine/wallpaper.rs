@@ -0,0 +1,468 @@
+use nanocv::ImgSize;
+use std::cmp::{min, Ordering};
+use std::f64::consts::PI;
+use super::pattern::{MosaicPattern, PointMapper, central_square};
+
+/// Plane symmetry groups, the patch is used as a fundamental region
+/// that is transformed to fill the unit cell of the group lattice
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WallpaperGroup {
+    P1, P2, Pm, Pg, Cm, Pmm, Pmg, Pgg, Cmm, P4, P4m, P4g, P3, P3m1, P31m, P6, P6m,
+}
+
+// Groups are named by their international (crystallographic) short names,
+// titles add a short description
+named_enum!(WallpaperGroup, "wallpaper group", [
+    P1 => ("p1", "p1 (translation)"),
+    P2 => ("p2", "p2 (180° rotation)"),
+    Pm => ("pm", "pm (mirror)"),
+    Pg => ("pg", "pg (glide reflection)"),
+    Cm => ("cm", "cm (mirror and glide)"),
+    Pmm => ("pmm", "pmm (perpendicular mirrors)"),
+    Pmg => ("pmg", "pmg (mirror and perpendicular glide)"),
+    Pgg => ("pgg", "pgg (perpendicular glides)"),
+    Cmm => ("cmm", "cmm (centered perpendicular mirrors)"),
+    P4 => ("p4", "p4 (90° rotation)"),
+    P4m => ("p4m", "p4m (90° rotation, diagonal mirrors)"),
+    P4g => ("p4g", "p4g (90° rotation, glides)"),
+    P3 => ("p3", "p3 (120° rotation)"),
+    P3m1 => ("p3m1", "p3m1 (120° rotation, mirrors)"),
+    P31m => ("p31m", "p31m (120° rotation, offset mirrors)"),
+    P6 => ("p6", "p6 (60° rotation)"),
+    P6m => ("p6m", "p6m (60° rotation, mirrors)"),
+]);
+
+impl WallpaperGroup {
+    fn lattice(self) -> Lattice {
+        use WallpaperGroup::*;
+        use Transform::*;
+        match self {
+            P1 => Lattice::Rectangular(Tiles::new(1, 1, &[Identity])),
+            P2 => Lattice::Rectangular(Tiles::new(2, 1, &[Identity, Rotate180])),
+            Pm => Lattice::Rectangular(Tiles::new(2, 1, &[Identity, MirrorX])),
+            Pg => Lattice::Rectangular(Tiles::new(1, 2, &[Identity, MirrorX])),
+            Cm => Lattice::Rectangular(Tiles::new(2, 2, &[
+                Identity, MirrorX, 
+                MirrorX, Identity
+            ])),
+            Pmm => Lattice::Rectangular(Tiles::new(2, 2, &[
+                Identity, MirrorX, 
+                MirrorY, Rotate180
+            ])),
+            Pmg => Lattice::Rectangular(Tiles::new(2, 2, &[
+                Identity, MirrorX, 
+                Rotate180, MirrorY
+            ])),
+            Pgg => Lattice::Rectangular(Tiles::new(2, 2, &[
+                Identity, MirrorY, 
+                MirrorX, Rotate180
+            ])),
+            // Rectangular pmm blocks alternating with their copies rotated
+            // by 180° place 2-fold rotation centers off the mirror lines
+            Cmm => Lattice::Rectangular(Tiles::new(4, 4, &[
+                Identity, MirrorX, Rotate180, MirrorY,
+                MirrorY, Rotate180, MirrorX, Identity,
+                Rotate180, MirrorY, Identity, MirrorX,
+                MirrorX, Identity, MirrorY, Rotate180,
+            ])),
+            P4 => Lattice::Square(Fold::None, Tiles::new(2, 2, &[
+                Identity, Rotate90, 
+                Rotate270, Rotate180
+            ])),
+            P4m => Lattice::Square(Fold::Diagonal, Tiles::new(2, 2, &[
+                Identity, MirrorX, 
+                MirrorY, Rotate180
+            ])),
+            P4g => Lattice::Square(Fold::AntiDiagonal, Tiles::new(2, 2, &[
+                Identity, Rotate90, 
+                Rotate270, Rotate180
+            ])),
+            P3 => Lattice::Hexagonal(HexFold::Rotation(3)),
+            P3m1 => Lattice::Hexagonal(HexFold::Mirror(3, PI/6.0)),
+            P31m => Lattice::Hexagonal(HexFold::Mirror(3, 0.0)),
+            P6 => Lattice::Hexagonal(HexFold::Rotation(6)),
+            P6m => Lattice::Hexagonal(HexFold::Mirror(6, 0.0)),
+        }
+    }
+}
+
+impl MosaicPattern for WallpaperGroup {
+    fn cell_size(&self, patch: ImgSize) -> ImgSize {
+        match self.lattice() {
+            Lattice::Rectangular(tiles) => ImgSize::new(patch.x*tiles.cols, patch.y*tiles.rows),
+            Lattice::Square(_, tiles) => {
+                let side = min(patch.x, patch.y);
+                ImgSize::new(side*tiles.cols, side*tiles.rows)
+            },
+            Lattice::Hexagonal(fold) => hex_cell_size(fold.bounds(), patch),
+        }
+    }
+
+    fn map_point(&self, patch: ImgSize, x: f64, y: f64) -> (f64, f64) {
+        match self.lattice() {
+            Lattice::Rectangular(tiles) => {
+                tiles.map_point(patch.x as f64, patch.y as f64, x, y)
+            },
+            Lattice::Square(fold, tiles) => {
+                let (side, offset) = central_square(patch);
+                let (u, v) = tiles.map_point(side, side, x, y);
+                let (u, v) = fold.apply(side, u, v);
+                (u + offset.0, v + offset.1)
+            },
+            Lattice::Hexagonal(_) => self.point_mapper(patch)(x, y),
+        }
+    }
+
+    fn point_mapper<'a>(&'a self, patch: ImgSize) -> PointMapper<'a> {
+        match self.lattice() {
+            Lattice::Hexagonal(fold) => {
+                // Sector bounds are sampled along the hexagon border,
+                // too expensive to be repeated for every pixel
+                let bounds = fold.bounds();
+                let cell = hex_cell_size(bounds, patch);
+                Box::new(move |x, y| map_hex_point(fold, bounds, patch, cell, x, y))
+            },
+            _ => Box::new(move |x, y| self.map_point(patch, x, y)),
+        }
+    }
+
+    fn interpolated(&self) -> bool {
+        match self.lattice() {
+            Lattice::Hexagonal(_) => true,
+            _ => false,
+        }
+    }
+}
+
+enum Lattice {
+    Rectangular(Tiles),
+    Square(Fold, Tiles),
+    Hexagonal(HexFold),
+}
+
+/// Grid of transformed patch copies forming the unit cell, row by row
+struct Tiles {
+    cols: usize,
+    rows: usize,
+    transforms: &'static [Transform],
+}
+
+impl Tiles {
+    fn new(cols: usize, rows: usize, transforms: &'static [Transform]) -> Self {
+        Self {cols, rows, transforms}
+    }
+
+    fn map_point(&self, w: f64, h: f64, x: f64, y: f64) -> (f64, f64) {
+        let col = min((x/w).max(0.0) as usize, self.cols - 1);
+        let row = min((y/h).max(0.0) as usize, self.rows - 1);
+        let (u, v) = (x - col as f64*w, y - row as f64*h);
+        self.transforms[row*self.cols + col].apply(w, h, u, v)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Transform {
+    Identity,
+    MirrorX,
+    MirrorY,
+    Rotate180,
+    /// Rotation by 90° clockwise, valid for square tiles only
+    Rotate90,
+    /// Rotation by 270° clockwise, valid for square tiles only
+    Rotate270,
+}
+
+impl Transform {
+    /// Maps a point of the transformed tile into the original tile
+    fn apply(self, w: f64, h: f64, u: f64, v: f64) -> (f64, f64) {
+        match self {
+            Transform::Identity => (u, v),
+            Transform::MirrorX => (w - u, v),
+            Transform::MirrorY => (u, h - v),
+            Transform::Rotate180 => (w - u, h - v),
+            Transform::Rotate90 => (v, w - u),
+            Transform::Rotate270 => (h - v, u),
+        }
+    }
+}
+
+/// Makes a square tile symmetric by reflecting one of its triangles
+#[derive(Clone, Copy)]
+enum Fold {
+    None,
+    /// Reflection along the diagonal from the top left corner
+    Diagonal,
+    /// Reflection along the diagonal from the top right corner
+    AntiDiagonal,
+}
+
+impl Fold {
+    fn apply(self, side: f64, u: f64, v: f64) -> (f64, f64) {
+        match self {
+            Fold::Diagonal if v > u => (v, u),
+            Fold::AntiDiagonal if u + v > side => (side - v, side - u),
+            _ => (u, v),
+        }
+    }
+}
+
+const SQRT_3: f64 = 1.7320508075688772;
+
+/// Symmetry around each point of the hexagonal lattice
+#[derive(Clone, Copy)]
+enum HexFold {
+    /// N-fold rotation
+    Rotation(u32),
+    /// N-fold rotation with mirror lines, first line at the given angle
+    Mirror(u32, f64),
+}
+
+impl HexFold {
+    /// Maps angle into fundamental sector starting at returned offset
+    fn sector(self) -> (f64, f64) {
+        match self {
+            HexFold::Rotation(n) => (0.0, 2.0*PI/n as f64),
+            HexFold::Mirror(n, offset) => (offset, PI/n as f64),
+        }
+    }
+
+    fn apply(self, angle: f64) -> f64 {
+        match self {
+            HexFold::Rotation(n) => angle.rem_euclid(2.0*PI/n as f64),
+            HexFold::Mirror(n, offset) => {
+                let period = 2.0*PI/n as f64;
+                let angle = (angle - offset).rem_euclid(period);
+                offset + if angle > period/2.0 { period - angle } else { angle }
+            }
+        }
+    }
+
+    /// Bounding box of the fundamental sector within the hexagon around
+    /// lattice point, lattice constant is 1
+    fn bounds(self) -> (f64, f64, f64, f64) {
+        let (start, size) = self.sector();
+        let steps = 24;
+        let mut bounds = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+
+        for step in 0..=steps {
+            let angle = start + size*step as f64/steps as f64;
+            let radius = hexagon_radius(angle);
+            let (x, y) = (radius*angle.cos(), radius*angle.sin());
+            bounds = (bounds.0.min(x), bounds.1.min(y), bounds.2.max(x), bounds.3.max(y));
+        }
+
+        bounds
+    }
+}
+
+/// Distance from the hexagon center to its border in the given direction,
+/// hexagon edges are perpendicular to directions 0°, 60°, 120°...
+fn hexagon_radius(angle: f64) -> f64 {
+    let sector = PI/3.0;
+    let relative = (angle + sector/2.0).rem_euclid(sector) - sector/2.0;
+    0.5/relative.cos()
+}
+
+/// Size of the unit cell in pixels so that the fundamental sector
+/// with given bounds is as wide as the patch
+fn hex_cell_size(bounds: (f64, f64, f64, f64), patch: ImgSize) -> ImgSize {
+    let (x1, _, x2, _) = bounds;
+    let width = patch.x as f64/(x2 - x1);
+
+    ImgSize::new(
+        width.round().max(1.0) as usize, 
+        (width*SQRT_3).round().max(1.0) as usize
+    )
+}
+
+/// Maps point of the rectangular unit cell of hexagonal lattice into
+/// the fundamental sector around the nearest lattice point and then
+/// stretches the sector bounding box over the patch
+fn map_hex_point(
+    fold: HexFold, bounds: (f64, f64, f64, f64), patch: ImgSize, cell: ImgSize, x: f64, y: f64
+) -> (f64, f64) {
+    let lx = x/cell.x as f64;
+    let ly = y/cell.y as f64*SQRT_3;
+    let (cx, cy) = nearest_lattice_point(lx, ly);
+    let (dx, dy) = (lx - cx, ly - cy);
+    let radius = (dx*dx + dy*dy).sqrt();
+    let angle = fold.apply(dy.atan2(dx));
+    let (fx, fy) = (radius*angle.cos(), radius*angle.sin());
+    let (x1, y1, x2, y2) = bounds;
+
+    (
+        (fx - x1)/(x2 - x1)*patch.x as f64, 
+        (fy - y1)/(y2 - y1)*patch.y as f64
+    )
+}
+
+/// Finds nearest point of hexagonal lattice with lattice constant 1,
+/// the rectangular unit cell 1 x sqrt(3) contains lattice points
+/// in its corners and center
+fn nearest_lattice_point(x: f64, y: f64) -> (f64, f64) {
+    let candidates = [
+        (0.0, 0.0), (1.0, 0.0), (0.0, SQRT_3), (1.0, SQRT_3), (0.5, SQRT_3/2.0)
+    ];
+
+    let distance = |point: &(f64, f64)| (point.0 - x).powi(2) + (point.1 - y).powi(2);
+
+    candidates.iter().cloned()
+        .min_by(|a, b| distance(a).partial_cmp(&distance(b)).unwrap_or(Ordering::Equal))
+        .unwrap_or((0.0, 0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::named::Named;
+    use super::WallpaperGroup::*;
+
+    const W: f64 = 40.0;
+    const H: f64 = 30.0;
+    /// Side of the square tile cut from the patch
+    const S: f64 = 30.0;
+
+    fn patch() -> ImgSize {
+        ImgSize::new(W as usize, H as usize)
+    }
+
+    /// Pattern at any point of the plane, the unit cell repeats
+    fn sample(group: WallpaperGroup, x: f64, y: f64) -> (f64, f64) {
+        let cell = group.cell_size(patch());
+        group.map_point(patch(), x.rem_euclid(cell.x as f64), y.rem_euclid(cell.y as f64))
+    }
+
+    /// Points spread over the unit cell, away from tile borders
+    fn points(group: WallpaperGroup) -> Vec<(f64, f64)> {
+        let cell = group.cell_size(patch());
+
+        (0..200).map(|i| i as f64).map(|i| (
+            (i*0.618_034 + 0.123).fract()*cell.x as f64,
+            (i*0.414_214 + 0.071).fract()*cell.y as f64,
+        )).collect()
+    }
+
+    fn assert_invariant(group: WallpaperGroup, symmetry: impl Fn(f64, f64) -> (f64, f64)) {
+        for (x, y) in points(group) {
+            let (sx, sy) = symmetry(x, y);
+            let (a, b) = (sample(group, x, y), sample(group, sx, sy));
+
+            assert!(
+                (a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6,
+                "{} maps ({}, {}) to {:?} and ({}, {}) to {:?}", 
+                group.name(), x, y, a, sx, sy, b
+            );
+        }
+    }
+
+    /// Converts symmetry given in hexagonal lattice coordinates into pixels
+    /// of the unit cell, lattice constant is 1
+    fn in_lattice(
+        group: WallpaperGroup, symmetry: impl Fn(f64, f64) -> (f64, f64)
+    ) -> impl Fn(f64, f64) -> (f64, f64) {
+        let cell = group.cell_size(patch());
+        let (w, h) = (cell.x as f64, cell.y as f64/SQRT_3);
+
+        move |x, y| {
+            let (lx, ly) = symmetry(x/w, y/h);
+            (lx*w, ly*h)
+        }
+    }
+
+    /// Lattice point in the center of the unit cell
+    const CENTER: (f64, f64) = (0.5, SQRT_3/2.0);
+
+    fn rotation(angle: f64) -> impl Fn(f64, f64) -> (f64, f64) {
+        move |x, y| {
+            let (dx, dy) = (x - CENTER.0, y - CENTER.1);
+            let (sin, cos) = angle.sin_cos();
+            (CENTER.0 + dx*cos - dy*sin, CENTER.1 + dx*sin + dy*cos)
+        }
+    }
+
+    /// Reflection along line through the lattice point at given angle
+    fn reflection(angle: f64) -> impl Fn(f64, f64) -> (f64, f64) {
+        move |x, y| {
+            let (dx, dy) = (x - CENTER.0, y - CENTER.1);
+            let (sin, cos) = (2.0*angle).sin_cos();
+            (CENTER.0 + dx*cos + dy*sin, CENTER.1 + dx*sin - dy*cos)
+        }
+    }
+
+    #[test]
+    fn cell_contains_transformed_patches() {
+        let expected = [
+            (P1, (40, 30)), (P2, (80, 30)), (Pm, (80, 30)), (Pg, (40, 60)), (Cm, (80, 60)),
+            (Pmm, (80, 60)), (Pmg, (80, 60)), (Pgg, (80, 60)), (Cmm, (160, 120)),
+            (P4, (60, 60)), (P4m, (60, 60)), (P4g, (60, 60)),
+            (P3, (53, 92)), (P3m1, (80, 139)), (P31m, (80, 139)), (P6, (80, 139)),
+            (P6m, (80, 139)),
+        ];
+
+        assert_eq!(expected.len(), WallpaperGroup::all().len());
+
+        for &(group, size) in expected.iter() {
+            let cell = group.cell_size(patch());
+            assert_eq!((cell.x, cell.y), size, "{}", group.name());
+        }
+    }
+
+    #[test]
+    fn points_map_into_patch() {
+        for group in WallpaperGroup::all() {
+            for (x, y) in points(group) {
+                let (u, v) = group.map_point(patch(), x, y);
+                assert!((-1e-9..=W + 1e-9).contains(&u), "{} x {}", group.name(), u);
+                assert!((-1e-9..=H + 1e-9).contains(&v), "{} y {}", group.name(), v);
+            }
+        }
+    }
+
+    #[test]
+    fn rectangular_groups_are_invariant_under_generators() {
+        assert_invariant(P2, |x, y| (2.0*W - x, H - y));
+        assert_invariant(Pm, |x, y| (2.0*W - x, y));
+        assert_invariant(Pg, |x, y| (W - x, y + H));
+        assert_invariant(Cm, |x, y| (2.0*W - x, y));
+        assert_invariant(Cm, |x, y| (x + W, y + H));
+        assert_invariant(Pmm, |x, y| (2.0*W - x, y));
+        assert_invariant(Pmm, |x, y| (x, 2.0*H - y));
+        assert_invariant(Pmg, |x, y| (2.0*W - x, y));
+        assert_invariant(Pmg, |x, y| (W - x, 2.0*H - y));
+        assert_invariant(Pgg, |x, y| (x + W, H - y));
+        assert_invariant(Pgg, |x, y| (W - x, y + H));
+        assert_invariant(Pgg, |x, y| (2.0*W - x, 2.0*H - y));
+        assert_invariant(Cmm, |x, y| (2.0*W - x, y));
+        assert_invariant(Cmm, |x, y| (x, 2.0*H - y));
+        assert_invariant(Cmm, |x, y| (x + 2.0*W, y + 2.0*H));
+        assert_invariant(Cmm, |x, y| (4.0*W - x, 4.0*H - y));
+    }
+
+    #[test]
+    fn square_groups_are_invariant_under_generators() {
+        let rotate_90 = |x: f64, y: f64| (2.0*S - y, x);
+        assert_invariant(P4, rotate_90);
+        assert_invariant(P4m, |x, y| (2.0*S - x, y));
+        assert_invariant(P4m, |x, y| (x, 2.0*S - y));
+        assert_invariant(P4m, |x, y| (y, x));
+        assert_invariant(P4g, rotate_90);
+        assert_invariant(P4g, |x, y| (S - y, S - x));
+    }
+
+    #[test]
+    fn hexagonal_groups_are_invariant_under_generators() {
+        for &group in [P3, P3m1, P31m, P6, P6m].iter() {
+            assert_invariant(group, in_lattice(group, |x, y| (x + CENTER.0, y + CENTER.1)));
+        }
+
+        assert_invariant(P3, in_lattice(P3, rotation(2.0*PI/3.0)));
+        assert_invariant(P3m1, in_lattice(P3m1, rotation(2.0*PI/3.0)));
+        assert_invariant(P3m1, in_lattice(P3m1, reflection(PI/6.0)));
+        assert_invariant(P31m, in_lattice(P31m, rotation(2.0*PI/3.0)));
+        assert_invariant(P31m, in_lattice(P31m, reflection(0.0)));
+        assert_invariant(P6, in_lattice(P6, rotation(PI/3.0)));
+        assert_invariant(P6m, in_lattice(P6m, rotation(PI/3.0)));
+        assert_invariant(P6m, in_lattice(P6m, reflection(0.0)));
+        assert_invariant(P6m, in_lattice(P6m, reflection(PI/6.0)));
+    }
+}
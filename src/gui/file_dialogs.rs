@@ -1,4 +1,5 @@
 use gtk::*;
+use nanomosaic::PROJECT_SUFFIX;
 
 pub fn open_file_dialog(window: ApplicationWindow) -> Option<String> {
     let open_dialog = FileChooserDialog::with_buttons(
@@ -41,6 +42,63 @@ pub fn save_file_dialog(window: ApplicationWindow) -> Option<String> {
     }
 }
 
+pub fn open_project_dialog(window: ApplicationWindow) -> Option<String> {
+    let open_dialog = FileChooserDialog::with_buttons(
+        "Open project", Some(&window), FileChooserAction::Open,
+        &[
+            ("_Cancel", ResponseType::Cancel), 
+            ("_Open", ResponseType::Accept)
+        ]
+    );
+
+    open_dialog.add_filter(&create_project_filter());
+
+    let result = open_dialog.clone().run();    
+    open_dialog.close();
+
+    match result {
+        -3 => Some(open_dialog.get_filename()?.to_str()?.to_owned()),
+        _ => None
+    }
+}
+
+/// Asks for project file name, suffix is appended when missing
+pub fn save_project_dialog(window: ApplicationWindow) -> Option<String> {
+    let save_dialog = FileChooserDialog::with_buttons(
+        "Save project", Some(&window), FileChooserAction::Save,
+        &[
+            ("_Cancel", ResponseType::Cancel), 
+            ("_Save", ResponseType::Accept)
+        ]
+    );
+
+    save_dialog.add_filter(&create_project_filter());
+    save_dialog.set_do_overwrite_confirmation(true);
+
+    let result = save_dialog.clone().run();    
+    save_dialog.close();
+
+    match result {
+        -3 => {
+            let path = save_dialog.get_filename()?.to_str()?.to_owned();
+            let suffix = format!(".{}", PROJECT_SUFFIX);
+
+            match path.ends_with(&suffix) {
+                true => Some(path),
+                false => Some(path + &suffix),
+            }
+        },
+        _ => None
+    }
+}
+
+fn create_project_filter() -> FileFilter {
+    let filter = FileFilter::new();
+    filter.add_pattern(&format!("*.{}", PROJECT_SUFFIX));
+    filter.set_name("Mosaic projects");
+    filter
+}
+
 fn create_image_filter() -> FileFilter {
     let filter = FileFilter::new();
     filter.add_mime_type("image/png");
@@ -4,5 +4,9 @@ mod window;
 mod pixbuf;
 mod components;
 mod file_dialogs;
+mod save_dialog;
+mod options;
+mod selection;
+mod progress;
 
 pub use window::build_ui;
\ No newline at end of file
@@ -0,0 +1,154 @@
+use gtk::*;
+use std::rc::Rc;
+use nanomosaic::{PatternId, MosaicOptions, Filter, SeamBlend, Named};
+use crate::message::*;
+
+/// Widgets editing mosaic options, changes are sent to the logic thread
+pub struct OptionsPanel {
+    pub container: Box,
+    pattern: ComboBoxText,
+    segments: SpinButton,
+    repeat_x: SpinButton,
+    repeat_y: SpinButton,
+    filter: ComboBoxText,
+    blend: ComboBoxText,
+    blend_width: SpinButton,
+}
+
+impl OptionsPanel {
+    pub fn new(logic: LogicSender) -> Self {
+        let pattern = create_pattern_selector(logic.clone());
+        let segments = create_segments_selector(logic.clone());
+        let (repeat_x, repeat_y) = create_repeat_selectors(logic.clone());
+        let filter = create_filter_selector(logic.clone());
+        let (blend, blend_width) = create_blend_selectors(logic.clone());
+
+        let container = Box::new(Orientation::Horizontal, 0);
+        container.pack_start(&Label::new("Pattern"), false, false, 5);
+        container.pack_start(&pattern, false, false, 5);
+        container.pack_start(&Label::new("Segments"), false, false, 5);
+        container.pack_start(&segments, false, false, 5);
+        container.pack_start(&Label::new("Repeat"), false, false, 5);
+        container.pack_start(&repeat_x, false, false, 5);
+        container.pack_start(&Label::new("x"), false, false, 0);
+        container.pack_start(&repeat_y, false, false, 5);
+        container.pack_start(&Label::new("Quality"), false, false, 5);
+        container.pack_start(&filter, false, false, 5);
+        container.pack_start(&Label::new("Seams"), false, false, 5);
+        container.pack_start(&blend, false, false, 5);
+        container.pack_start(&blend_width, false, false, 5);
+
+        Self {container, pattern, segments, repeat_x, repeat_y, filter, blend, blend_width}
+    }
+
+    /// Displays options changed by the logic thread, e.g. loaded from project
+    pub fn show(&self, options: &MosaicOptions) {
+        self.pattern.set_active_id(Some(options.pattern.name()));
+        self.segments.set_value(options.segments as f64);
+        self.repeat_x.set_value(options.repeat.0 as f64);
+        self.repeat_y.set_value(options.repeat.1 as f64);
+        self.filter.set_active_id(Some(options.filter.name()));
+        self.blend.set_active_id(Some(options.blend.name()));
+        self.blend_width.set_value(options.blend_width as f64);
+    }
+}
+
+fn create_pattern_selector(logic: LogicSender) -> ComboBoxText {
+    let combo = ComboBoxText::new();
+
+    for pattern in PatternId::all() {
+        combo.append(Some(pattern.name()), pattern.title());
+    }
+
+    combo.set_active_id(Some(PatternId::Mirror.name()));
+    combo.connect_changed(move |combo| {
+        let id = combo.get_active_id();
+
+        if let Some(pattern) = id.and_then(|id| PatternId::from_name(&id)) {
+            send(&logic, LogicMessage::SetPattern(pattern));
+        }
+    });
+    combo
+}
+
+fn create_segments_selector(logic: LogicSender) -> SpinButton {
+    let spin = SpinButton::new_with_range(2.0, 64.0, 2.0);
+    spin.set_value(MosaicOptions::default().segments as f64);
+    spin.connect_value_changed(move |spin| {
+        send(&logic, LogicMessage::SetSegments(spin.get_value_as_int() as u32));
+    });
+    spin
+}
+
+fn create_repeat_selectors(logic: LogicSender) -> (SpinButton, SpinButton) {
+    let repeat = MosaicOptions::default().repeat;
+    let spin_x = SpinButton::new_with_range(1.0, 100.0, 1.0);
+    let spin_y = SpinButton::new_with_range(1.0, 100.0, 1.0);
+    spin_x.set_value(repeat.0 as f64);
+    spin_y.set_value(repeat.1 as f64);
+
+    for spin in &[spin_x.clone(), spin_y.clone()] {
+        let (logic, spin_x, spin_y) = (logic.clone(), spin_x.clone(), spin_y.clone());
+
+        spin.connect_value_changed(move |_| {
+            let repeat = (spin_x.get_value_as_int() as usize, spin_y.get_value_as_int() as usize);
+            send(&logic, LogicMessage::SetRepeat(repeat));
+        });
+    }
+
+    (spin_x, spin_y)
+}
+
+fn create_filter_selector(logic: LogicSender) -> ComboBoxText {
+    let combo = ComboBoxText::new();
+
+    for filter in Filter::all() {
+        combo.append(Some(filter.name()), filter.title());
+    }
+
+    combo.set_active_id(Some(MosaicOptions::default().filter.name()));
+    combo.connect_changed(move |combo| {
+        let id = combo.get_active_id();
+
+        if let Some(filter) = id.and_then(|id| Filter::from_name(&id)) {
+            send(&logic, LogicMessage::SetFilter(filter));
+        }
+    });
+    combo
+}
+
+/// Seam blending of translation tiling and width of cross-faded borders
+fn create_blend_selectors(logic: LogicSender) -> (ComboBoxText, SpinButton) {
+    let defaults = MosaicOptions::default();
+    let combo = ComboBoxText::new();
+
+    for blend in SeamBlend::all() {
+        combo.append(Some(blend.name()), blend.title());
+    }
+
+    combo.set_active_id(Some(defaults.blend.name()));
+    combo.set_tooltip_text(Some("Blending of patch borders used by translation pattern"));
+
+    let spin = SpinButton::new_with_range(1.0, 1000.0, 1.0);
+    spin.set_value(defaults.blend_width as f64);
+    spin.set_tooltip_text(Some("Width of cross-faded borders in pixels"));
+
+    let update = {
+        let (combo, spin) = (combo.clone(), spin.clone());
+
+        move || {
+            let id = combo.get_active_id();
+
+            if let Some(blend) = id.and_then(|id| SeamBlend::from_name(&id)) {
+                let width = spin.get_value_as_int() as usize;
+                send(&logic, LogicMessage::SetBlend((blend, width)));
+            }
+        }
+    };
+
+    let update = Rc::new(update);
+    let on_spin = update.clone();
+    combo.connect_changed(move |_| update());
+    spin.connect_value_changed(move |_| on_spin());
+    (combo, spin)
+}
@@ -1,6 +1,6 @@
 use gdk_pixbuf::{Pixbuf, Colorspace};
-use std::{cell::RefCell, rc::Rc};
-use nanocv::{ImgBuf, ImgSize, Img};
+use nanocv::{ImgBuf, Img};
+use std::mem::ManuallyDrop;
 use crate::message::Rgba;
 
 pub fn create_pixbuf(width: usize, height: usize) -> Pixbuf {
@@ -9,43 +9,24 @@ pub fn create_pixbuf(width: usize, height: usize) -> Pixbuf {
     ).expect("No enough memory to create pixbuf.")
 }
 
-pub fn update_pixbuf(
-    image: &ImgBuf<Rgba>,
-    pixbuf: Rc<RefCell<Pixbuf>>,    
-) {
-    if pixbuf_size(pixbuf.clone()) != image.size() {
-        pixbuf.replace(create_pixbuf(image.width(), image.height()));
-    }    
-
-    copy_rgba_to_pixbuf(image, &pixbuf.borrow());
-}
-
-fn pixbuf_size(pixbuf: Rc<RefCell<Pixbuf>>) -> ImgSize {
-    let inner: &Pixbuf = &pixbuf.borrow();
-    ImgSize::new(inner.get_width() as usize, inner.get_height() as usize)
+/// Reinterprets pixels as RGBA bytes in pixbuf layout without copying
+fn into_bytes(pixels: Vec<Rgba>) -> Vec<u8> {
+    let mut pixels = ManuallyDrop::new(pixels);
+    let (ptr, len, capacity) = (pixels.as_mut_ptr(), pixels.len(), pixels.capacity());
+    // [u8; 4] has alignment of u8 and no padding, allocation size stays the same
+    unsafe { Vec::from_raw_parts(ptr as *mut u8, len*4, capacity*4) }
 }
 
-fn copy_rgba_to_pixbuf(image: &ImgBuf<Rgba>, pixbuf: &Pixbuf) {
-    let pixbuf_data = unsafe { pixbuf.get_pixels() };
-    let (w, h) = (image.width(), image.height());
-    let stride = pixbuf.get_rowstride() as usize;
-
-    for line in 0..h {
-        let line_pixels = image.line_ref(line);
-        let buf_pixels = &mut pixbuf_data[line*stride..line*stride + w*4];
+/// Creates pixbuf owning the image data, pixels are not copied
+pub fn pixbuf_from_image(image: ImgBuf<Rgba>) -> Pixbuf {
+    let (width, height) = (image.width() as i32, image.height() as i32);
 
-        let mut offset = 0;
-
-        while offset < buf_pixels.len() {
-            let pixel = line_pixels[offset/4];
-            let target = &mut buf_pixels[offset .. (offset + 4)];
-            target[0] = pixel[0];
-            target[1] = pixel[1];
-            target[2] = pixel[2];
-            target[3] = pixel[3];
-            offset += 4;
-        }        
+    if width == 0 || height == 0 {
+        return create_pixbuf(1, 1);
     }
+
+    let bytes = into_bytes(image.into_vec());
+    Pixbuf::new_from_vec(bytes, Colorspace::Rgb, true, 8, width, height, width*4)
 }
 
 pub fn horizontal_line(pixbuf: &Pixbuf, line: isize) {
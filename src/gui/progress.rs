@@ -0,0 +1,87 @@
+use gtk::*;
+use std::{rc::Rc, cell::RefCell, sync::{Arc, atomic::{AtomicBool, Ordering}}};
+use crate::message::*;
+use nanomosaic::SaveStage;
+
+/// Progress of the running save with Cancel button, 
+/// shows notification when the image is saved
+pub struct SavePanel {
+    pub container: Box,
+    label: Label,
+    bar: ProgressBar,
+    cancel_button: Button,
+    /// Cancel flag of the running save
+    cancel: Rc<RefCell<Option<SaveCancel>>>,
+    /// Button that started the running save, disabled until it finishes
+    save_button: RefCell<Option<Button>>,
+}
+
+impl SavePanel {
+    pub fn new() -> Self {
+        let label = Label::new("");
+        let bar = ProgressBar::new();
+        bar.set_show_text(true);
+        let cancel_button = Button::new();
+        cancel_button.add(&Label::new("Cancel"));
+        let cancel: Rc<RefCell<Option<SaveCancel>>> = Rc::new(RefCell::new(None));
+
+        let button_cancel = cancel.clone();
+        cancel_button.connect_clicked(move |button| {
+            if let Some(ref flag) = *button_cancel.borrow() {
+                flag.store(true, Ordering::SeqCst);
+            }
+            button.set_sensitive(false);
+        });
+
+        let container = Box::new(Orientation::Horizontal, 0);
+        container.pack_start(&label, false, false, 5);
+        container.pack_start(&bar, true, true, 5);
+        container.pack_start(&cancel_button, false, false, 5);
+
+        label.show();
+        bar.show();
+        cancel_button.show_all();
+
+        // Panel is displayed only while saving and after it
+        container.set_no_show_all(true);
+        Self {container, label, bar, cancel_button, cancel, save_button: RefCell::new(None)}
+    }
+
+    /// Shows progress of a new save and disables the button that started it
+    /// until the save finishes, returns cancel flag of the save
+    pub fn start(&self, save_button: &Button) -> SaveCancel {
+        save_button.set_sensitive(false);
+        *self.save_button.borrow_mut() = Some(save_button.clone());
+        let flag = Arc::new(AtomicBool::new(false));
+        *self.cancel.borrow_mut() = Some(flag.clone());
+        self.label.set_text("Saving image");
+        self.progress(SaveStage::Compose, 0.0);
+        self.bar.show();
+        self.cancel_button.set_sensitive(true);
+        self.cancel_button.show();
+        self.container.show();
+        flag
+    }
+
+    pub fn progress(&self, stage: SaveStage, done: f64) {
+        self.bar.set_fraction(done.max(0.0).min(1.0));
+        self.bar.set_text(Some(&format!("{} {:.0} %", stage.title(), done*100.0)));
+    }
+
+    pub fn finished(&self, outcome: SaveOutcome) {
+        *self.cancel.borrow_mut() = None;
+
+        if let Some(button) = self.save_button.borrow_mut().take() {
+            button.set_sensitive(true);
+        }
+
+        self.bar.hide();
+        self.cancel_button.hide();
+
+        match outcome {
+            SaveOutcome::Saved(path) => self.label.set_text(&format!("Image saved into {}", path)),
+            SaveOutcome::Cancelled => self.label.set_text("Save cancelled"),
+            SaveOutcome::Failed => self.container.hide(),
+        }
+    }
+}
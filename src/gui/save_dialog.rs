@@ -0,0 +1,232 @@
+use gtk::*;
+use std::{rc::Rc, cell::Cell};
+use nanocv::ImgSize;
+use nanomosaic::{OutputOptions, OutputKind, MosaicOptions, MM_PER_INCH, DEFAULT_DPI, Named};
+
+/// Asks for the kind, size and resolution of the saved image, `output` options
+/// of the last save or loaded project are offered, native size created
+/// from the patch is offered when they do not specify the size
+pub fn save_options_dialog(
+    window: ApplicationWindow, patch: ImgSize, mosaic: MosaicOptions, output: OutputOptions
+) -> Option<OutputOptions> {
+    let dialog = Dialog::new_with_buttons(
+        Some("Save options"), Some(&window), DialogFlags::MODAL,
+        &[
+            ("_Cancel", ResponseType::Cancel), 
+            ("_Save", ResponseType::Accept)
+        ]
+    );
+
+    let editor = Rc::new(SizeEditor::new(patch, mosaic, output));
+    SizeEditor::connect(editor.clone());
+    dialog.get_content_area().pack_start(&editor.grid, true, true, 5);
+    dialog.show_all();
+
+    let result = dialog.run();    
+    dialog.close();
+
+    match result {
+        -3 => Some(editor.options()),
+        _ => None
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Unit {
+    Pixels,
+    Millimeters,
+    Inches,
+}
+
+impl Unit {
+    fn from_id(id: &str) -> Unit {
+        match id {
+            "mm" => Unit::Millimeters,
+            "in" => Unit::Inches,
+            _ => Unit::Pixels,
+        }
+    }
+}
+
+struct SizeEditor {
+    grid: Grid,
+    kind: ComboBoxText,
+    width: SpinButton,
+    height: SpinButton,
+    dpi: SpinButton,
+    /// DPI is written into file metadata if checked
+    write_dpi: CheckButton,
+    unit: ComboBoxText,
+    keep_aspect: CheckButton,
+    sidecar: CheckButton,
+    patch: ImgSize,
+    mosaic: MosaicOptions,
+    aspect: Cell<f64>,
+    /// Current size in pixels
+    pixels: Cell<(f64, f64)>,
+    /// Set while values are updated programmatically
+    updating: Cell<bool>,
+}
+
+impl SizeEditor {
+    fn new(patch: ImgSize, mosaic: MosaicOptions, output: OutputOptions) -> Self {
+        let kind = ComboBoxText::new();
+
+        for output_kind in OutputKind::all() {
+            kind.append(Some(output_kind.name()), output_kind.title());
+        }
+
+        kind.set_active_id(Some(output.kind.name()));
+        let size = output.target_size(output.kind.native_size(&mosaic, patch));
+
+        let width = SpinButton::new_with_range(1.0, 1_000_000.0, 1.0);
+        let height = SpinButton::new_with_range(1.0, 1_000_000.0, 1.0);
+        let dpi = SpinButton::new_with_range(1.0, 10_000.0, 1.0);
+        let unit = ComboBoxText::new();
+        let keep_aspect = CheckButton::new_with_label("Keep aspect ratio");
+        let sidecar = CheckButton::new_with_label("Save tiling metadata (.json)");
+        let write_dpi = CheckButton::new_with_label("Write DPI into file");
+
+        unit.append(Some("px"), "pixels");
+        unit.append(Some("mm"), "millimeters");
+        unit.append(Some("in"), "inches");
+        unit.set_active_id(Some("px"));
+        keep_aspect.set_active(true);
+        write_dpi.set_active(output.dpi.is_some());
+        sidecar.set_active(output.sidecar);
+        width.set_value(size.x as f64);
+        height.set_value(size.y as f64);
+        dpi.set_value(output.dpi.unwrap_or(DEFAULT_DPI));
+
+        let grid = Grid::new();
+        grid.set_row_spacing(5);
+        grid.set_column_spacing(5);
+        grid.attach(&Label::new("Output"), 0, 0, 1, 1);
+        grid.attach(&kind, 1, 0, 1, 1);
+        grid.attach(&Label::new("Width"), 0, 1, 1, 1);
+        grid.attach(&width, 1, 1, 1, 1);
+        grid.attach(&Label::new("Height"), 0, 2, 1, 1);
+        grid.attach(&height, 1, 2, 1, 1);
+        grid.attach(&Label::new("Unit"), 0, 3, 1, 1);
+        grid.attach(&unit, 1, 3, 1, 1);
+        grid.attach(&Label::new("DPI"), 0, 4, 1, 1);
+        grid.attach(&dpi, 1, 4, 1, 1);
+        grid.attach(&write_dpi, 0, 5, 2, 1);
+        grid.attach(&keep_aspect, 0, 6, 2, 1);
+        grid.attach(&sidecar, 0, 7, 2, 1);
+
+        Self {
+            grid, kind, width, height, dpi, write_dpi, unit, keep_aspect, sidecar, patch, mosaic,
+            aspect: Cell::new(size.x as f64/size.y.max(1) as f64),
+            pixels: Cell::new((size.x as f64, size.y as f64)),
+            updating: Cell::new(false),
+        }
+    }
+
+    fn connect(editor: Rc<SizeEditor>) {
+        let clone = editor.clone();
+        editor.width.connect_value_changed(move |_| clone.size_changed(true));
+        let clone = editor.clone();
+        editor.height.connect_value_changed(move |_| clone.size_changed(false));
+        let clone = editor.clone();
+        editor.dpi.connect_value_changed(move |_| clone.dpi_changed());
+        let clone = editor.clone();
+        editor.unit.connect_changed(move |_| clone.unit_changed());
+        let clone = editor.clone();
+        editor.kind.connect_changed(move |_| clone.kind_changed());
+    }
+
+    fn output_kind(&self) -> OutputKind {
+        self.kind.get_active_id()
+            .and_then(|id| OutputKind::from_name(&id))
+            .unwrap_or_default()
+    }
+
+    /// Native size of the selected output kind is offered
+    fn kind_changed(&self) {
+        let size = self.output_kind().native_size(&self.mosaic, self.patch);
+        self.aspect.set(size.x as f64/size.y.max(1) as f64);
+        self.pixels.set((size.x as f64, size.y as f64));
+        self.show_size();
+    }
+
+    fn unit(&self) -> Unit {
+        Unit::from_id(&self.unit.get_active_id().map(|id| id.to_string()).unwrap_or_default())
+    }
+
+    fn to_pixels(&self, value: f64) -> f64 {
+        match self.unit() {
+            Unit::Pixels => value,
+            Unit::Millimeters => value/MM_PER_INCH*self.dpi.get_value(),
+            Unit::Inches => value*self.dpi.get_value(),
+        }
+    }
+
+    fn from_pixels(&self, value: f64) -> f64 {
+        match self.unit() {
+            Unit::Pixels => value,
+            Unit::Millimeters => value/self.dpi.get_value()*MM_PER_INCH,
+            Unit::Inches => value/self.dpi.get_value(),
+        }
+    }
+
+    fn size_changed(&self, width_changed: bool) {
+        if self.updating.get() { return; }
+
+        let mut width = self.to_pixels(self.width.get_value());
+        let mut height = self.to_pixels(self.height.get_value());
+
+        if self.keep_aspect.get_active() {
+            match width_changed {
+                true => height = width/self.aspect.get(),
+                false => width = height*self.aspect.get(),
+            }
+        }
+
+        self.pixels.set((width, height));
+        self.show_size();
+    }
+
+    fn dpi_changed(&self) {
+        // Physical size is preserved, pixel size is preserved otherwise
+        if self.unit() != Unit::Pixels {
+            let width = self.to_pixels(self.width.get_value());
+            let height = self.to_pixels(self.height.get_value());
+            self.pixels.set((width, height));
+        }
+    }
+
+    fn unit_changed(&self) {
+        let digits = match self.unit() {
+            Unit::Pixels => 0,
+            _ => 2,
+        };
+
+        self.width.set_digits(digits);
+        self.height.set_digits(digits);
+        self.show_size();
+    }
+
+    fn show_size(&self) {
+        let (width, height) = self.pixels.get();
+        self.updating.set(true);
+        self.width.set_value(self.from_pixels(width));
+        self.height.set_value(self.from_pixels(height));
+        self.updating.set(false);
+    }
+
+    fn options(&self) -> OutputOptions {
+        let (width, height) = self.pixels.get();
+
+        OutputOptions {
+            kind: self.output_kind(),
+            width: Some(width.round().max(1.0) as usize),
+            height: Some(height.round().max(1.0) as usize),
+            dpi: match self.write_dpi.get_active() {
+                true => Some(self.dpi.get_value()),
+                false => None,
+            },
+            sidecar: self.sidecar.get_active(),
+        }
+    }
+}
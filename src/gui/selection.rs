@@ -0,0 +1,139 @@
+use gtk::*;
+use std::{rc::Rc, cell::Cell};
+use crate::message::*;
+
+/// Spin buttons editing selection in source image pixels, 
+/// kept in sync with selection made by mouse
+pub struct SelectionPanel {
+    pub container: Box,
+    x: SpinButton,
+    y: SpinButton,
+    width: SpinButton,
+    height: SpinButton,
+    /// Set while values received from the logic thread are displayed
+    updating: Rc<Cell<bool>>,
+}
+
+impl SelectionPanel {
+    pub fn new(logic: LogicSender) -> Self {
+        let x = create_spin_button(0.0);
+        let y = create_spin_button(0.0);
+        let width = create_spin_button(1.0);
+        let height = create_spin_button(1.0);
+        let updating = Rc::new(Cell::new(false));
+
+        for spin in &[x.clone(), y.clone(), width.clone(), height.clone()] {
+            let (logic, updating) = (logic.clone(), updating.clone());
+            let (x, y, width, height) = (x.clone(), y.clone(), width.clone(), height.clone());
+
+            spin.connect_value_changed(move |_| {
+                if updating.get() { return; }
+
+                let rect = SelectionRect {
+                    x: x.get_value_as_int() as isize,
+                    y: y.get_value_as_int() as isize,
+                    width: width.get_value_as_int() as isize,
+                    height: height.get_value_as_int() as isize,
+                };
+
+                send(&logic, LogicMessage::SetSelection(rect));
+            });
+        }
+
+        let constraint = create_constraint_selector(logic.clone());
+        let refine = create_refine_button(logic.clone());
+
+        let container = Box::new(Orientation::Horizontal, 0);
+        container.pack_start(&Label::new("X"), false, false, 5);
+        container.pack_start(&x, false, false, 5);
+        container.pack_start(&Label::new("Y"), false, false, 5);
+        container.pack_start(&y, false, false, 5);
+        container.pack_start(&Label::new("Width"), false, false, 5);
+        container.pack_start(&width, false, false, 5);
+        container.pack_start(&Label::new("Height"), false, false, 5);
+        container.pack_start(&height, false, false, 5);
+        container.pack_start(&Label::new("Constraint"), false, false, 5);
+        container.pack_start(&constraint, false, false, 5);
+        container.pack_start(&refine, false, false, 5);
+
+        Self {container, x, y, width, height, updating}
+    }
+
+    pub fn show(&self, rect: SelectionRect) {
+        self.updating.set(true);
+        self.x.set_value(rect.x as f64);
+        self.y.set_value(rect.y as f64);
+        self.width.set_value(rect.width as f64);
+        self.height.set_value(rect.height as f64);
+        self.updating.set(false);
+    }
+}
+
+/// Constraint selector with width and height used by custom ratio and fixed size
+fn create_constraint_selector(logic: LogicSender) -> Box {
+    let combo = ComboBoxText::new();
+    combo.append(Some("free"), "Free");
+    combo.append(Some("1:1"), "Square 1:1");
+    combo.append(Some("4:3"), "Ratio 4:3");
+    combo.append(Some("ratio"), "Custom ratio");
+    combo.append(Some("fixed"), "Fixed size");
+    combo.append(Some("pow2"), "Power of two");
+    combo.set_active_id(Some("free"));
+
+    let width = create_spin_button(1.0);
+    let height = create_spin_button(1.0);
+    width.set_value(256.0);
+    height.set_value(256.0);
+
+    let update = {
+        let (combo, width, height) = (combo.clone(), width.clone(), height.clone());
+
+        move || {
+            let id = combo.get_active_id();
+            let (x, y) = (width.get_value_as_int(), height.get_value_as_int());
+            let custom = id.as_ref().map(|id| id == "ratio" || id == "fixed");
+            width.set_sensitive(custom == Some(true));
+            height.set_sensitive(custom == Some(true));
+
+            let constraint = match id.as_ref().map(|id| id.as_str()) {
+                Some("1:1") => SelectionConstraint::AspectRatio((1, 1)),
+                Some("4:3") => SelectionConstraint::AspectRatio((4, 3)),
+                Some("ratio") => SelectionConstraint::AspectRatio((x as u32, y as u32)),
+                Some("fixed") => SelectionConstraint::FixedSize((x as isize, y as isize)),
+                Some("pow2") => SelectionConstraint::PowerOfTwo,
+                _ => SelectionConstraint::Free,
+            };
+
+            send(&logic, LogicMessage::SetConstraint(constraint));
+        }
+    };
+
+    let update = Rc::new(update);
+    let (on_combo, on_width, on_height) = (update.clone(), update.clone(), update.clone());
+    combo.connect_changed(move |_| on_combo());
+    width.connect_value_changed(move |_| on_width());
+    height.connect_value_changed(move |_| on_height());
+    width.set_sensitive(false);
+    height.set_sensitive(false);
+
+    let container = Box::new(Orientation::Horizontal, 0);
+    container.pack_start(&combo, false, false, 5);
+    container.pack_start(&width, false, false, 5);
+    container.pack_start(&Label::new(":"), false, false, 0);
+    container.pack_start(&height, false, false, 5);
+    container
+}
+
+fn create_refine_button(logic: LogicSender) -> Button {
+    let button = Button::new();
+    button.add(&Label::new("Auto-refine"));
+    button.set_tooltip_text(Some("Move selection nearby to minimize visible seams"));
+    button.connect_clicked(move |_| send(&logic, LogicMessage::RefineSelection));
+    button
+}
+
+fn create_spin_button(min: f64) -> SpinButton {
+    let spin = SpinButton::new_with_range(min, 1_000_000.0, 1.0);
+    spin.set_width_chars(6);
+    spin
+}
@@ -1,15 +1,20 @@
 use gtk::*;
-use std::{rc::Rc, cell::RefCell};
+use std::{rc::Rc, cell::{Cell, RefCell}};
 use glib::{MainContext};
 use super::components::*;
 use gdk_pixbuf::{Pixbuf};
 use super::{
-    file_dialogs::{save_file_dialog, open_file_dialog}, 
-    pixbuf::{update_pixbuf, create_pixbuf, horizontal_line, vertical_line}
+    file_dialogs::{save_file_dialog, open_file_dialog, save_project_dialog, open_project_dialog}, 
+    save_dialog::save_options_dialog,
+    options::OptionsPanel,
+    selection::SelectionPanel,
+    progress::SavePanel,
+    pixbuf::{pixbuf_from_image, create_pixbuf, horizontal_line, vertical_line}
 };
 use crate::{common::log_err, message::*};
 use nanocv::{ImgSize, ImgBuf};
-use gdk::EventButton;
+use nanomosaic::{MosaicOptions, OutputOptions, DEFAULT_DPI};
+use gdk::{EventButton, EventKey};
 
 pub fn build_ui(
     app: &Application, 
@@ -24,6 +29,8 @@ pub fn build_ui(
     send(&logic, LogicMessage::InitGui(logic_gui_tx));
     send(&composite, CompositeMessage::InitGui(composite_gui_tx));
 
+    let output = OutputOptions::from_physical(None, None, DEFAULT_DPI);
+    let patch = Rc::new(Cell::new((ImgSize::new(1, 1), MosaicOptions::default(), output)));
     let select_pixbuf = Rc::new(RefCell::new(create_pixbuf(1, 1)));
     let result_pixbuf = Rc::new(RefCell::new(create_pixbuf(1, 1)));
 
@@ -31,44 +38,64 @@ pub fn build_ui(
     let window = create_window(app);
 
     let (select_image, select_events, select_box) = create_images(logic.clone(), ImageId::Select);
-    let (result_image, _, result_box) = create_images(logic.clone(), ImageId::Result);
+    let (result_image, result_events, result_box) = create_images(logic.clone(), ImageId::Result);
 
+    connect_view_navigation(select_events.clone(), logic.clone(), ImageId::Select);
     connect_image_mouse_down(select_events.clone(), logic.clone());
+    connect_image_mouse_up(select_events.clone(), logic.clone());
     connect_image_mouse_move(select_events.clone(), logic.clone());
+    connect_view_navigation(result_events.clone(), logic.clone(), ImageId::Result);
 
     splitter.pack1(&select_box, false, false);
     splitter.pack2(&result_box, true, true);
 
     let load_button = create_load_button(logic.clone(), window.clone());
-    let save_button = create_save_button(logic.clone(), window.clone());
+    let save_panel = Rc::new(SavePanel::new());
+    let save_button = create_save_button(
+        logic.clone(), window.clone(), patch.clone(), save_panel.clone()
+    );
+    let open_project_button = create_open_project_button(logic.clone(), window.clone());
+    let save_project_button = create_save_project_button(logic.clone(), window.clone());
+    let options_panel = Rc::new(OptionsPanel::new(logic.clone()));
+    let selection_panel = Rc::new(SelectionPanel::new(logic.clone()));
 
     let top_panel = Box::new(Orientation::Horizontal, 0);
     top_panel.pack_start(&load_button, false, false, 5);
     top_panel.pack_start(&save_button, false, false, 5);
+    top_panel.pack_start(&open_project_button, false, false, 5);
+    top_panel.pack_start(&save_project_button, false, false, 5);
+    top_panel.pack_start(&options_panel.container, false, false, 0);
 
     let main_panel = Box::new(Orientation::Vertical, 0);
     main_panel.pack_start(&top_panel, false, false, 5);
+    main_panel.pack_start(&selection_panel.container, false, false, 5);
     main_panel.pack_start(&splitter, true, true, 5);
+    main_panel.pack_start(&save_panel.container, false, false, 5);
 
+    connect_shortcuts(window.clone(), logic.clone());
     window.add(&main_panel);
     window.show_all();
     window.maximize();
 
-    let message_logic_sender = logic.clone();
     let message_select_image = select_image.clone();
     let message_result_image = result_image.clone();
+    let message_select_events = select_events.clone();
 
     let window_clone = window.clone();
 
     gui_rx.attach(None, move |message: GuiMessage| {
         process_message(
             window_clone.clone(),
-            message_logic_sender.clone(),
             message, 
             select_pixbuf.clone(),
             result_pixbuf.clone(),
             message_select_image.clone(),
             message_result_image.clone(),
+            message_select_events.clone(),
+            patch.clone(),
+            options_panel.clone(),
+            selection_panel.clone(),
+            save_panel.clone(),
         );
 
         glib::Continue(true)
@@ -92,12 +119,45 @@ fn create_load_button(logic: LogicSender, window: ApplicationWindow) -> Button {
     button    
 }
 
-fn create_save_button(logic: LogicSender, window: ApplicationWindow) -> Button {
+fn create_save_button(
+    logic: LogicSender, 
+    window: ApplicationWindow, 
+    patch: Rc<Cell<(ImgSize, MosaicOptions, OutputOptions)>>,
+    save_panel: Rc<SavePanel>,
+) -> Button {
     let button = Button::new();
     button.add(&Label::new("Save image"));
-    button.connect_clicked(move |_| {
+    button.connect_clicked(move |button| {
         if let Some(path) = save_file_dialog(window.clone()) {
-            send(&logic, LogicMessage::SaveImage(path));
+            let (patch_size, options, output) = patch.get();
+            let output = save_options_dialog(window.clone(), patch_size, options, output);
+
+            if let Some(output) = output {
+                let cancel = save_panel.start(button);
+                send(&logic, LogicMessage::SaveImage((path, output, cancel)));
+            }
+        }
+    });   
+    button    
+}
+
+fn create_open_project_button(logic: LogicSender, window: ApplicationWindow) -> Button {
+    let button = Button::new();
+    button.add(&Label::new("Open project"));
+    button.connect_clicked(move |_| {
+        if let Some(path) = open_project_dialog(window.clone()) {
+            send(&logic, LogicMessage::LoadProject(path));
+        }
+    });   
+    button    
+}
+
+fn create_save_project_button(logic: LogicSender, window: ApplicationWindow) -> Button {
+    let button = Button::new();
+    button.add(&Label::new("Save project"));
+    button.connect_clicked(move |_| {
+        if let Some(path) = save_project_dialog(window.clone()) {
+            send(&logic, LogicMessage::SaveProject(path));
         }
     });   
     button    
@@ -128,39 +188,153 @@ fn connect_image_mouse_down(image: EventBox, logic: LogicSender) {
     });
 }
 
+fn connect_image_mouse_up(image: EventBox, logic: LogicSender) {
+    image.connect_button_release_event(move |_image, event: &EventButton| {
+        send(&logic, LogicMessage::MouseUp(event.get_button()));
+        Inhibit(true)
+    });
+}
+
 fn connect_image_mouse_move(image: EventBox, logic: LogicSender) {
     image.connect_motion_notify_event(move |_image, event| {
         let (x, y) = event.get_position();
+        send(&logic, LogicMessage::MouseMove((x, y)));
+        Inhibit(true)
+    });
+}
+
+/// Zoom factor for one step of mouse wheel
+const ZOOM_STEP: f64 = 1.25;
+
+/// Mouse wheel zooms the view, middle button drag pans it,
+/// needs to be connected before other mouse handlers
+fn connect_view_navigation(image: EventBox, logic: LogicSender, id: ImageId) {
+    let last_point = Rc::new(Cell::new((0.0, 0.0)));
+
+    let scroll_logic = logic.clone();
+    image.connect_scroll_event(move |_image, event| {
+        let (x, y) = event.get_position();
 
-        if event.get_state().contains(gdk::ModifierType::BUTTON1_MASK) {
-            send(&logic, LogicMessage::MouseDown((1, x, y)));
+        let steps = match event.get_direction() {
+            gdk::ScrollDirection::Up => 1.0,
+            gdk::ScrollDirection::Down => -1.0,
+            gdk::ScrollDirection::Smooth => -event.get_delta().1,
+            _ => return Inhibit(false),
+        };
+
+        send(&scroll_logic, LogicMessage::Zoom((id, ZOOM_STEP.powf(steps), x, y)));
+        Inhibit(true)
+    });
+
+    let press_point = last_point.clone();
+    image.connect_button_press_event(move |_image, event: &EventButton| {
+        if event.get_button() == 2 {
+            press_point.set(event.get_position());
         }
+        Inhibit(false)
+    });
 
-        if event.get_state().contains(gdk::ModifierType::BUTTON3_MASK) {
-            send(&logic, LogicMessage::MouseDown((3, x, y)));
+    image.connect_motion_notify_event(move |_image, event| {
+        if !event.get_state().contains(gdk::ModifierType::BUTTON2_MASK) {
+            return Inhibit(false);
         }
 
+        let (x, y) = event.get_position();
+        let (last_x, last_y) = last_point.get();
+        last_point.set((x, y));
+        send(&logic, LogicMessage::Pan((id, x - last_x, y - last_y)));
         Inhibit(true)
     });
 }
 
+fn set_cursor(widget: &EventBox, handle: Handle) {
+    let window = match widget.get_window() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let cursor_type = match handle {
+        Handle::New => gdk::CursorType::Crosshair,
+        Handle::Move => gdk::CursorType::Fleur,
+        Handle::Left => gdk::CursorType::LeftSide,
+        Handle::Right => gdk::CursorType::RightSide,
+        Handle::Top => gdk::CursorType::TopSide,
+        Handle::Bottom => gdk::CursorType::BottomSide,
+        Handle::TopLeft => gdk::CursorType::TopLeftCorner,
+        Handle::TopRight => gdk::CursorType::TopRightCorner,
+        Handle::BottomLeft => gdk::CursorType::BottomLeftCorner,
+        Handle::BottomRight => gdk::CursorType::BottomRightCorner,
+    };
+
+    let cursor = gdk::Cursor::new_for_display(&window.get_display(), cursor_type);
+    window.set_cursor(Some(&cursor));
+}
+
+/// Selection step in source pixels when Control is held with arrow keys
+const LARGE_STEP: isize = 10;
+
+fn connect_shortcuts(window: ApplicationWindow, logic: LogicSender) {
+    window.connect_key_press_event(move |window, event: &EventKey| {
+        let state = event.get_state();
+        let control = state.contains(gdk::ModifierType::CONTROL_MASK);
+        let shift = state.contains(gdk::ModifierType::SHIFT_MASK);
+        let key = event.get_keyval();
+        let z = key == gdk::enums::key::z || key == gdk::enums::key::Z;
+
+        match (control, shift, z) {
+            (true, false, true) => send(&logic, LogicMessage::Undo),
+            (true, true, true) => send(&logic, LogicMessage::Redo),
+            _ => return arrow_key_pressed(window, &logic, key, control, shift),
+        }
+
+        Inhibit(true)
+    });
+}
+
+/// Arrows move the selection, Shift + arrows resize it, 
+/// Control makes larger steps
+fn arrow_key_pressed(
+    window: &ApplicationWindow, logic: &LogicSender, key: u32, control: bool, shift: bool
+) -> Inhibit {
+    // Keep arrow keys working in text entries and spin buttons
+    if window.get_focus().map_or(false, |widget| widget.is::<Entry>()) {
+        return Inhibit(false);
+    }
+
+    let step = if control { LARGE_STEP } else { 1 };
+
+    let (dx, dy) = match key {
+        gdk::enums::key::Left => (-step, 0),
+        gdk::enums::key::Right => (step, 0),
+        gdk::enums::key::Up => (0, -step),
+        gdk::enums::key::Down => (0, step),
+        _ => return Inhibit(false),
+    };
+
+    match shift {
+        true => send(logic, LogicMessage::ResizeSelection((dx, dy))),
+        false => send(logic, LogicMessage::MoveSelection((dx, dy))),
+    }
+
+    Inhibit(true)
+}
+
 fn process_message(
     window: ApplicationWindow,
-    logic: LogicSender,
     message: GuiMessage, 
     select_pixbuf: Rc<RefCell<Pixbuf>>, 
     result_pixbuf: Rc<RefCell<Pixbuf>>,
     select_image: Image, 
     result_image: Image,
+    select_events: EventBox,
+    patch: Rc<Cell<(ImgSize, MosaicOptions, OutputOptions)>>,
+    options_panel: Rc<OptionsPanel>,
+    selection_panel: Rc<SelectionPanel>,
+    save_panel: Rc<SavePanel>,
 ) {
     match message {
-        GuiMessage::RenderSource(image) => {
-            update_image(select_image, select_pixbuf, &image);
-            send(&logic, LogicMessage::ReturnBuffer(image));
-        }
-        GuiMessage::RenderTarget(data) => {
-            update_image(result_image, result_pixbuf, &data);
-        },
+        GuiMessage::RenderSource(data) => update_image(select_image, select_pixbuf, data),
+        GuiMessage::RenderTarget(data) => update_image(result_image, result_pixbuf, data),
         GuiMessage::RenderLines(lines) => {
             horizontal_line(&select_pixbuf.borrow(), lines.y1);
             horizontal_line(&select_pixbuf.borrow(), lines.y2);
@@ -169,6 +343,10 @@ fn process_message(
             let inner: &Pixbuf = &select_pixbuf.borrow();
             select_image.set_from_pixbuf(Some(inner));
         },
+        GuiMessage::PatchSize(size) => patch.set(size),
+        GuiMessage::ShowOptions(options) => options_panel.show(&options),
+        GuiMessage::ShowSelection(rect) => selection_panel.show(rect),
+        GuiMessage::SetCursor(handle) => set_cursor(&select_events, handle),
         GuiMessage::ShowError(message) => {
             let dialog = MessageDialog::new(Some(&window),
                 DialogFlags::empty(),
@@ -180,11 +358,14 @@ fn process_message(
             dialog.run();            
             dialog.close();
         }
+        GuiMessage::SaveProgress((stage, done)) => save_panel.progress(stage, done),
+        GuiMessage::SaveFinished(outcome) => save_panel.finished(outcome),
     }
 }
 
-fn update_image(image: Image, pixbuf: Rc<RefCell<Pixbuf>>, data: &ImgBuf<Rgba>) {
-    update_pixbuf(&data, pixbuf.clone());
+/// Image data are moved into the pixbuf
+fn update_image(image: Image, pixbuf: Rc<RefCell<Pixbuf>>, data: ImgBuf<Rgba>) {
+    pixbuf.replace(pixbuf_from_image(data));
     let inner: &Pixbuf = &pixbuf.borrow();
     image.set_from_pixbuf(Some(inner));
 }
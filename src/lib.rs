@@ -0,0 +1,18 @@
+//! Mosaic engine loading images, selecting patches and composing mosaics
+//!
+//! The engine does not depend on GTK, the `nanomosaic` application
+//! is a thin user interface built on top of it.
+
+mod engine;
+
+pub use engine::{
+    Rgba, load_image, save_image, select_patch, 
+    create_mosaic, create_texture, render_mosaic_view, save_mosaic, save_mosaic_with_progress,
+    resize, resize_factor,
+    MosaicPattern, PointMapper, PatternId, 
+    Mirror, MirrorX, MirrorY, Rotation, Translation, PointReflection,
+    WallpaperGroup, Kaleidoscope, MosaicOptions, OutputOptions, OutputKind, MM_PER_INCH,
+    Filter, resample, resample_tileable, Project, PROJECT_SUFFIX, refine_selection, 
+    SeamBlend, heal_seams, offset_and_heal, TilingMetadata, SaveProgress, SaveStage, NoProgress,
+    Named, DEFAULT_DPI,
+};
@@ -0,0 +1,215 @@
+use std::cmp::{min, max};
+use crate::message::{Handle, SelectionRect, SelectionConstraint};
+
+/// Distance in screen pixels within which selection edges can be grabbed
+const GRAB_DISTANCE: f64 = 6.0;
+
+/// Finds selection handle under the mouse pointer, 
+/// selection edges are given in screen pixels as left, top, right, bottom
+pub fn hit_test(edges: (f64, f64, f64, f64), x: f64, y: f64) -> Handle {
+    let (left, top, right, bottom) = edges;
+    let inside_x = x > left - GRAB_DISTANCE && x < right + GRAB_DISTANCE;
+    let inside_y = y > top - GRAB_DISTANCE && y < bottom + GRAB_DISTANCE;
+
+    if !inside_x || !inside_y {
+        return Handle::New;
+    }
+
+    let near_left = (x - left).abs() <= GRAB_DISTANCE;
+    let near_right = (x - right).abs() <= GRAB_DISTANCE && !near_left;
+    let near_top = (y - top).abs() <= GRAB_DISTANCE;
+    let near_bottom = (y - bottom).abs() <= GRAB_DISTANCE && !near_top;
+
+    handle_from_edges(near_left, near_right, near_top, near_bottom)
+}
+
+/// Applies mouse movement in source pixels to the selection grabbed 
+/// by the handle, result is normalized and limited to image bounds.
+/// Returned handle is flipped if edges were dragged across each other.
+pub fn drag(
+    handle: Handle, 
+    rect: SelectionRect, 
+    dx: isize, 
+    dy: isize, 
+    bounds: (isize, isize)
+) -> (SelectionRect, Handle) {
+    let (w, h) = bounds;
+
+    if handle == Handle::Move {
+        let x = max(0, min(rect.x + dx, w - rect.width));
+        let y = max(0, min(rect.y + dy, h - rect.height));
+        return (SelectionRect {x, y, ..rect}, handle);
+    }
+
+    let (mut left, mut top) = (rect.x, rect.y);
+    let (mut right, mut bottom) = (rect.x + rect.width, rect.y + rect.height);
+
+    if handle.moves_left() { left += dx; }
+    if handle.moves_right() { right += dx; }
+    if handle.moves_top() { top += dy; }
+    if handle.moves_bottom() { bottom += dy; }
+
+    let (flip_x, flip_y) = (left > right, top > bottom);
+    let handle = handle_from_edges(
+        if flip_x { handle.moves_right() } else { handle.moves_left() },
+        if flip_x { handle.moves_left() } else { handle.moves_right() },
+        if flip_y { handle.moves_bottom() } else { handle.moves_top() },
+        if flip_y { handle.moves_top() } else { handle.moves_bottom() },
+    );
+
+    let (left, right) = (clamp(min(left, right), w), clamp(max(left, right), w));
+    let (top, bottom) = (clamp(min(top, bottom), h), clamp(max(top, bottom), h));
+    let rect = SelectionRect {x: left, y: top, width: right - left, height: bottom - top};
+    (rect, handle)
+}
+
+fn clamp(value: isize, size: isize) -> isize {
+    max(0, min(value, size))
+}
+
+/// Applies selection constraint to the rectangle edited by the handle,
+/// edges not moved by the handle are kept in place
+pub fn constrain(
+    constraint: SelectionConstraint,
+    handle: Handle,
+    rect: SelectionRect,
+    bounds: (isize, isize)
+) -> SelectionRect {
+    if constraint == SelectionConstraint::Free || handle == Handle::Move {
+        return rect;
+    }
+
+    let (w, h) = bounds;
+    let (right, bottom) = (rect.x + rect.width, rect.y + rect.height);
+    // Selection dragged to the image border keeps at least one pixel
+    let max_width = max(1, if handle.moves_left() { right } else { w - rect.x });
+    let max_height = max(1, if handle.moves_top() { bottom } else { h - rect.y });
+    let horizontal = handle.moves_left() || handle.moves_right();
+    let vertical = handle.moves_top() || handle.moves_bottom();
+
+    let (width, height) = match constraint {
+        SelectionConstraint::Free => (rect.width, rect.height),
+        SelectionConstraint::FixedSize((width, height)) => 
+            (min(width, max_width), min(height, max_height)),
+        SelectionConstraint::PowerOfTwo => (
+            power_of_two(rect.width, max_width), 
+            power_of_two(rect.height, max_height)
+        ),
+        SelectionConstraint::AspectRatio((ratio_x, ratio_y)) => {
+            let ratio = max(1, ratio_x) as f64/max(1, ratio_y) as f64;
+
+            // Dimension not changed by the handle follows the dragged one
+            let width = match (horizontal, vertical) {
+                (true, false) => rect.width as f64,
+                (false, true) => rect.height as f64*ratio,
+                _ => (rect.width as f64).max(rect.height as f64*ratio),
+            };
+
+            let width = width.min(max_width as f64).min(max_height as f64*ratio);
+            (width.round() as isize, (width/ratio).round() as isize)
+        }
+    };
+
+    let (width, height) = (min(width, max(1, w)), min(height, max(1, h)));
+    let x = if handle.moves_left() { right - width } else { rect.x };
+    let y = if handle.moves_top() { bottom - height } else { rect.y };
+
+    // Minimal selection at the border is shifted back into the image
+    SelectionRect {
+        x: clamp(x, w - width),
+        y: clamp(y, h - height),
+        width, 
+        height
+    }
+}
+
+/// Handle moving the given selection edges
+pub fn handle_from_edges(left: bool, right: bool, top: bool, bottom: bool) -> Handle {
+    match (left, right, top, bottom) {
+        (true, _, true, _) => Handle::TopLeft,
+        (true, _, _, true) => Handle::BottomLeft,
+        (_, true, true, _) => Handle::TopRight,
+        (_, true, _, true) => Handle::BottomRight,
+        (true, _, _, _) => Handle::Left,
+        (_, true, _, _) => Handle::Right,
+        (_, _, true, _) => Handle::Top,
+        (_, _, _, true) => Handle::Bottom,
+        _ => Handle::Move,
+    }
+}
+
+/// Nearest power of two not exceeding the limit
+fn power_of_two(value: isize, limit: isize) -> isize {
+    let mut result = 1;
+
+    while result*2 <= limit && 2*value >= 3*result {
+        result *= 2;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDS: (isize, isize) = (100, 80);
+
+    fn rect(x: isize, y: isize, width: isize, height: isize) -> SelectionRect {
+        SelectionRect {x, y, width, height}
+    }
+
+    #[test]
+    fn aspect_ratio_follows_dragged_edge() {
+        let aspect = SelectionConstraint::AspectRatio((2, 1));
+        let constrained = constrain(aspect, Handle::Right, rect(10, 10, 40, 5), BOUNDS);
+        assert_eq!(constrained, rect(10, 10, 40, 20));
+        let constrained = constrain(aspect, Handle::Bottom, rect(10, 10, 5, 15), BOUNDS);
+        assert_eq!(constrained, rect(10, 10, 30, 15));
+    }
+
+    #[test]
+    fn aspect_ratio_is_limited_by_image_border() {
+        let aspect = SelectionConstraint::AspectRatio((2, 1));
+        let limited = constrain(aspect, Handle::BottomRight, rect(70, 10, 30, 30), BOUNDS);
+        assert_eq!(limited, rect(70, 10, 30, 15));
+        // Edges not moved by the handle stay in place
+        let limited = constrain(aspect, Handle::TopLeft, rect(0, 0, 50, 10), BOUNDS);
+        assert_eq!(limited, rect(30, 0, 20, 10));
+    }
+
+    #[test]
+    fn power_of_two_rounds_to_nearest() {
+        let power = SelectionConstraint::PowerOfTwo;
+        let constrained = constrain(power, Handle::BottomRight, rect(5, 5, 50, 20), BOUNDS);
+        assert_eq!(constrained, rect(5, 5, 64, 16));
+    }
+
+    #[test]
+    fn power_of_two_fits_into_image_border() {
+        let power = SelectionConstraint::PowerOfTwo;
+        let limited = constrain(power, Handle::BottomRight, rect(90, 70, 10, 10), BOUNDS);
+        assert_eq!(limited, rect(90, 70, 8, 8));
+        // Selection collapsed at the border keeps one pixel inside the image
+        let limited = constrain(power, Handle::BottomRight, rect(100, 80, 0, 0), BOUNDS);
+        assert_eq!(limited, rect(99, 79, 1, 1));
+    }
+
+    #[test]
+    fn dragged_edges_flip_handle() {
+        let (dragged, handle) = drag(Handle::Left, rect(10, 10, 20, 20), 30, 0, BOUNDS);
+        assert_eq!((dragged, handle), (rect(30, 10, 10, 20), Handle::Right));
+
+        let (dragged, handle) = drag(Handle::TopLeft, rect(10, 10, 20, 20), 200, 200, BOUNDS);
+        assert_eq!((dragged, handle), (rect(30, 30, 70, 50), Handle::BottomRight));
+    }
+
+    #[test]
+    fn flipped_edges_are_clamped_to_image_border() {
+        let (dragged, handle) = drag(Handle::Right, rect(80, 10, 10, 10), -100, 0, BOUNDS);
+        assert_eq!((dragged, handle), (rect(0, 10, 80, 10), Handle::Left));
+
+        let (dragged, handle) = drag(Handle::Bottom, rect(10, 60, 10, 10), 0, -100, BOUNDS);
+        assert_eq!((dragged, handle), (rect(10, 0, 10, 60), Handle::Top));
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+
+/// Bounded history of states supporting undo and redo, 
+/// the oldest states are dropped when capacity is exceeded
+pub struct History<T> {
+    undo: VecDeque<T>,
+    redo: Vec<T>,
+    capacity: usize,
+}
+
+impl<T> History<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {undo: VecDeque::new(), redo: Vec::new(), capacity}
+    }
+
+    /// Records state preceding a modification, redo states are discarded
+    pub fn push(&mut self, state: T) {
+        if self.undo.len() >= self.capacity {
+            self.undo.pop_front();
+        }
+
+        self.undo.push_back(state);
+        self.redo.clear();
+    }
+
+    /// Returns previous state, current state becomes available for redo
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let previous = self.undo.pop_back()?;
+        self.redo.push(current);
+        Some(previous)
+    }
+
+    /// Returns state reverted by undo, current state becomes available for undo
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let next = self.redo.pop()?;
+        self.undo.push_back(current);
+        Some(next)
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oldest_states_are_dropped_over_capacity() {
+        let mut history = History::new(3);
+
+        for state in 1..=5 {
+            history.push(state);
+        }
+
+        assert_eq!(history.undo(6), Some(5));
+        assert_eq!(history.undo(5), Some(4));
+        assert_eq!(history.undo(4), Some(3));
+        assert_eq!(history.undo(3), None);
+    }
+
+    #[test]
+    fn undo_and_redo_restore_states() {
+        let mut history = History::new(10);
+        history.push(1);
+        history.push(2);
+
+        assert_eq!(history.undo(3), Some(2));
+        assert_eq!(history.undo(2), Some(1));
+        assert_eq!(history.redo(1), Some(2));
+        assert_eq!(history.redo(2), Some(3));
+        assert_eq!(history.redo(3), None);
+    }
+
+    #[test]
+    fn push_discards_redo_states() {
+        let mut history = History::new(10);
+        history.push(1);
+        assert_eq!(history.undo(2), Some(1));
+
+        history.push(1);
+        assert_eq!(history.redo(3), None);
+        assert_eq!(history.undo(3), Some(1));
+        assert_eq!(history.undo(1), None);
+    }
+}
@@ -1,4 +1,7 @@
 //! Logic thread responsible for managing application state and loading images
 mod state;
+mod history;
+mod handles;
+mod view;
 
 pub use state::LogicState;
\ No newline at end of file
@@ -1,21 +1,53 @@
 use log::*;
-use crate::common::{
-    convert_err, resize, resize_factor
-};
 use crate::message::*;
 use glib::{Sender as GlibSender};
-use image::{open};
-use nanocv::{ImgBuf, ImgSize, Img, Vec2d, Range2d, filter::map_range};
-use std::cmp::{min, max};
+use nanocv::{ImgBuf, ImgSize, Img, Vec2d, Range2d};
+use nanomosaic::{
+    load_image, select_patch, resample, refine_selection, 
+    PatternId, MosaicOptions, OutputOptions, Filter, Project, SeamBlend, DEFAULT_DPI,
+};
+use std::{cmp::{min, max}, sync::atomic::Ordering};
+use super::history::History;
+use super::handles::{hit_test, drag, constrain};
+use super::view::View;
+
+/// Number of selection changes that can be undone
+const HISTORY_SIZE: usize = 100;
+
+type Selection = (Vec2d<isize>, Vec2d<isize>);
+
+/// Selection drag in progress
+#[derive(Clone, Copy, Debug)]
+struct Drag {
+    handle: Handle,
+    /// Source image point where the drag started
+    origin: Vec2d<isize>,
+    /// Selection before the drag, stored to undo history when finished
+    initial: SelectionRect,
+    /// Selection the drag is applied to
+    base: SelectionRect,
+}
 
 pub struct LogicState {
     gui: Option<GlibSender<GuiMessage>>,
     image: ImgBuf<Rgba>,
+    image_path: Option<String>,
     select_size: ImgSize,
+    source_view: View,
     result_size: ImgSize,
+    result_view: View,
     compositor: CompositorSender,
+    /// Generation of the latest preview request
+    generation: u64,
+    latest_generation: LatestGeneration,
     start: Vec2d<isize>,
     end: Vec2d<isize>,
+    history: History<Selection>,
+    drag: Option<Drag>,
+    hover: Handle,
+    constraint: SelectionConstraint,
+    options: MosaicOptions,
+    output: OutputOptions,
     result_modified: bool,
     compositor_free: bool,
     source_modified: bool,
@@ -28,29 +60,54 @@ impl MessageReceiver<LogicMessage> for LogicState {
         use LogicMessage::*;
         match message {
             InitGui(channel) => Ok(self.init_gui(channel)),
-            LoadImage(path) => Ok(self.load_image(&path)),
+            LoadImage(path) => self.load_image(&path),
+            LoadProject(path) => Ok(self.load_project(&path)),
+            SaveProject(path) => Ok(self.save_project(&path)),
             ImageResized((id, size)) => Ok(self.image_resized(id, size)),
             MouseDown((button, x, y)) => Ok(self.mouse_down(button, x, y)),
-            CompositorFinished => {
-                self.compositor_free = true;
-                Ok(self.render_select_image())
-            },
-            ReturnBuffer(_image) => Ok(()),
-            SaveImage(path) => Ok(self.save_image(path)),
+            MouseMove((x, y)) => Ok(self.mouse_move(x, y)),
+            MouseUp(button) => Ok(self.mouse_up(button)),
+            Zoom((id, factor, x, y)) => Ok(self.zoom(id, factor, x, y)),
+            Pan((id, dx, dy)) => Ok(self.pan(id, dx, dy)),
+            SetSelection(rect) => Ok(self.set_selection_rect(rect)),
+            SetConstraint(constraint) => Ok(self.set_constraint(constraint)),
+            MoveSelection((dx, dy)) => Ok(self.move_selection(dx, dy)),
+            ResizeSelection((dx, dy)) => Ok(self.resize_selection(dx, dy)),
+            RefineSelection => Ok(self.refine_selection()),
+            Undo => Ok(self.undo()),
+            Redo => Ok(self.redo()),
+            SetPattern(pattern) => Ok(self.set_pattern(pattern)),
+            SetSegments(segments) => Ok(self.set_segments(segments)),
+            SetRepeat(repeat) => Ok(self.set_repeat(repeat)),
+            SetFilter(filter) => Ok(self.set_filter(filter)),
+            SetBlend((blend, width)) => Ok(self.set_blend(blend, width)),
+            CompositorFinished(generation) => Ok(self.compositor_finished(generation)),
+            SaveImage((path, output, cancel)) => Ok(self.save_image(path, output, cancel)),
         }
     }
 }
 
 impl LogicState {
-    pub fn new(compositor: CompositorSender) -> Self {
+    pub fn new(compositor: CompositorSender, latest_generation: LatestGeneration) -> Self {
         Self {
             gui: None,
             image: ImgBuf::<Rgba>::new_init(ImgSize::new(1, 1), [0, 0, 0, 0]),
+            image_path: None,
             select_size: ImgSize::new(1, 1),
+            source_view: View::new(),
             result_size: ImgSize::new(1, 1),
+            result_view: View::new(),
             compositor,
+            generation: 0,
+            latest_generation,
             start: Vec2d::new(0, 0),
             end: Vec2d::new(0, 0),
+            history: History::new(HISTORY_SIZE),
+            drag: None,
+            hover: Handle::New,
+            constraint: SelectionConstraint::Free,
+            options: MosaicOptions::default(),
+            output: OutputOptions::from_physical(None, None, DEFAULT_DPI),
             result_modified: true,
             compositor_free: true,
             source_modified: true,
@@ -60,19 +117,249 @@ impl LogicState {
     }
 
     fn mouse_down(&mut self, button: u32, x: f64, y: f64) {
-        let factor = resize_factor(self.image.size(), self.select_size);
-        let point = Vec2d::new((x as f64/factor) as isize, (y as f64/factor) as isize);
+        if button != 1 { return; }
+
+        let origin = self.source_point(x, y);
+        let initial = self.selection_rect();
+
+        let (handle, base) = match hit_test(self.screen_edges(), x, y) {
+            // New selection is dragged from a single point by its corner
+            Handle::New => (
+                Handle::BottomRight, 
+                SelectionRect {x: origin.x, y: origin.y, width: 0, height: 0}
+            ),
+            handle => (handle, initial),
+        };
 
-        match button {
-            1 => self.start = point,
-            3 => self.end = point,
-            _ => {}
+        self.drag = Some(Drag {handle, origin, initial, base});
+    }
+
+    fn mouse_move(&mut self, x: f64, y: f64) {
+        match self.drag {
+            Some(current) => {
+                let point = self.source_point(x, y);
+                let bounds = (self.image.width() as isize, self.image.height() as isize);
+                let (dx, dy) = (point.x - current.origin.x, point.y - current.origin.y);
+                let (rect, handle) = drag(current.handle, current.base, dx, dy, bounds);
+                let rect = constrain(self.constraint, handle, rect, bounds);
+
+                if rect != self.selection_rect() {
+                    self.set_selection(rect_to_selection(rect));
+                }
+            },
+            None => {
+                let handle = hit_test(self.screen_edges(), x, y);
+
+                if handle != self.hover {
+                    self.hover = handle;
+                    send_glib(&self.gui, GuiMessage::SetCursor(handle));
+                }
+            }
         }
+    }
 
+    fn mouse_up(&mut self, button: u32) {
+        if button != 1 { return; }
+
+        if let Some(finished) = self.drag.take() {
+            if finished.initial != self.selection_rect() {
+                self.history.push(rect_to_selection(finished.initial));
+            }
+        }
+    }
+
+    fn zoom(&mut self, id: ImageId, factor: f64, x: f64, y: f64) {
+        match id {
+            ImageId::Select => {
+                self.source_view.zoom_at(factor, x, y, self.image.size(), self.select_size);
+                self.source_view_changed();
+            },
+            ImageId::Result => {
+                let mosaic = self.mosaic_size();
+                self.result_view.zoom_at(factor, x, y, mosaic, self.result_size);
+                self.result_modified = true;
+                self.render_result_image();
+            }
+        }
+    }
+
+    fn pan(&mut self, id: ImageId, dx: f64, dy: f64) {
+        match id {
+            ImageId::Select => {
+                self.source_view.pan(dx, dy, self.image.size(), self.select_size);
+                self.source_view_changed();
+            },
+            ImageId::Result => {
+                let mosaic = self.mosaic_size();
+                self.result_view.pan(dx, dy, mosaic, self.result_size);
+                self.result_modified = true;
+                self.render_result_image();
+            }
+        }
+    }
+
+    fn source_view_changed(&mut self) {
+        self.last_source_size = None;
+        self.render_select_image();
+        // Handle under the mouse pointer may have changed
+        self.hover = Handle::New;
+    }
+
+    /// Converts point in the source view into source image coordinates
+    fn source_point(&self, x: f64, y: f64) -> Vec2d<isize> {
+        let (x, y) = self.source_view.to_image(x, y, self.image.size(), self.select_size);
+        Vec2d::new(x.round() as isize, y.round() as isize)
+    }
+
+    /// Converts source image point into the source view coordinates
+    fn screen_point(&self, x: isize, y: isize) -> (f64, f64) {
+        self.source_view.to_view(x as f64, y as f64, self.image.size(), self.select_size)
+    }
+
+    /// Selection left, top, right and bottom edges in the source view
+    fn screen_edges(&self) -> (f64, f64, f64, f64) {
+        let rect = self.selection_rect();
+        let (left, top) = self.screen_point(rect.x, rect.y);
+        let (right, bottom) = self.screen_point(rect.x + rect.width, rect.y + rect.height);
+        (left, top, right, bottom)
+    }
+
+    fn set_pattern(&mut self, pattern: PatternId) {
+        self.options.pattern = pattern;
+        self.result_modified = true;
+        self.render_result_image();
+    }
+
+    fn set_segments(&mut self, segments: u32) {
+        self.options.segments = segments;
+        self.result_modified = true;
+        self.render_result_image();
+    }
+
+    fn set_repeat(&mut self, repeat: (usize, usize)) {
+        self.options.repeat = repeat;
+        self.result_modified = true;
+        self.render_result_image();
+    }
+
+    fn set_filter(&mut self, filter: Filter) {
+        self.options.filter = filter;
+        self.last_source_size = None;
+        self.render_all();
+    }
+
+    fn set_blend(&mut self, blend: SeamBlend, width: usize) {
+        self.options.blend = blend;
+        self.options.blend_width = width;
+        self.result_modified = true;
+        self.render_result_image();
+    }
+
+    fn undo(&mut self) {
+        if let Some(selection) = self.history.undo((self.start, self.end)) {
+            self.set_selection(selection);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(selection) = self.history.redo((self.start, self.end)) {
+            self.set_selection(selection);
+        }
+    }
+
+    fn set_selection_rect(&mut self, rect: SelectionRect) {
+        let (w, h) = (self.image.width() as isize, self.image.height() as isize);
+        let clamped = self.clamp_to_image(rect);
+
+        // Dimension edited by user drives the constrained one
+        let current = self.selection_rect();
+        let handle = match (clamped.width != current.width, clamped.height != current.height) {
+            (true, false) => Handle::Right,
+            (false, true) => Handle::Bottom,
+            _ => Handle::BottomRight,
+        };
+        let clamped = constrain(self.constraint, handle, clamped, (w, h));
+
+        if clamped != self.selection_rect() {
+            self.history.push((self.start, self.end));
+            self.set_selection(rect_to_selection(clamped));
+        } else {
+            // Display clamped values in the GUI
+            self.send_selection();
+        }
+    }
+
+    /// Keeps the rectangle inside the image with at least one pixel selected
+    fn clamp_to_image(&self, rect: SelectionRect) -> SelectionRect {
+        let (w, h) = (self.image.width() as isize, self.image.height() as isize);
+        let x = max(0, min(rect.x, w - 1));
+        let y = max(0, min(rect.y, h - 1));
+
+        SelectionRect {
+            x, y,
+            width: min(max(1, rect.width), w - x),
+            height: min(max(1, rect.height), h - y),
+        }
+    }
+
+    fn move_selection(&mut self, dx: isize, dy: isize) {
+        let bounds = (self.image.width() as isize, self.image.height() as isize);
+        let current = self.selection_rect();
+        let (rect, _) = drag(Handle::Move, current, dx, dy, bounds);
+
+        if rect != current {
+            self.history.push((self.start, self.end));
+            self.set_selection(rect_to_selection(rect));
+        }
+    }
+
+    fn resize_selection(&mut self, dx: isize, dy: isize) {
+        let current = self.selection_rect();
+        self.set_selection_rect(SelectionRect {
+            width: current.width + dx,
+            height: current.height + dy,
+            ..current
+        });
+    }
+
+    fn refine_selection(&mut self) {
+        let current = self.selection_rect();
+        let pattern = self.options.create_pattern();
+        // Size is kept when constrained by user
+        let resize = self.constraint == SelectionConstraint::Free;
+        let rect = (current.x, current.y, current.width, current.height);
+        let (x, y, width, height) = refine_selection(&self.image, pattern.as_ref(), rect, resize);
+        self.set_selection_rect(SelectionRect {x, y, width, height});
+    }
+
+    fn set_constraint(&mut self, constraint: SelectionConstraint) {
+        self.constraint = constraint;
+        let rect = self.selection_rect();
+        self.set_selection_rect(rect);
+    }
+
+    fn selection_rect(&self) -> SelectionRect {
+        SelectionRect {
+            x: min(self.start.x, self.end.x),
+            y: min(self.start.y, self.end.y),
+            width: (self.end.x - self.start.x).abs(),
+            height: (self.end.y - self.start.y).abs(),
+        }
+    }
+
+    fn send_selection(&self) {
+        send_glib(&self.gui, GuiMessage::ShowSelection(self.selection_rect()));
+    }
+
+    fn set_selection(&mut self, selection: Selection) {
+        let (start, end) = selection;
+        self.start = start;
+        self.end = end;
         self.render_all();
     }
 
     fn render_all(&mut self) {
+        self.send_selection();
         self.source_modified = true;
         self.result_modified = true;
         self.render_select_image();
@@ -83,6 +370,7 @@ impl LogicState {
                 match id {
             ImageId::Select => if self.select_size != size {
                 self.select_size = size;
+                self.source_view.clamp(self.image.size(), size);
                 self.source_modified = true;
                 self.render_select_image();
             },
@@ -99,21 +387,74 @@ impl LogicState {
         debug!("Logic: GUI channel initialized.")        
     }
 
-    fn load_image(&mut self, path: &str) {
+    fn load_image(&mut self, path: &str) -> Result<(), String> {
         match load_image(&path) {
             Ok(img) => {
                 info!("Image {} x {} loaded", img.width(), img.height());        
                 self.image = img;
+                self.image_path = Some(path.to_owned());
                 self.start = Vec2d::new(0, 0);
                 self.end = self.image.range().end();
+                self.history.clear();
+                self.drag = None;
+                self.source_view = View::new();
+                self.result_view = View::new();
                 self.last_source_size = None;
                 self.last_rendered_lines = None;
                 self.render_all();
+                Ok(())
             },
-            Err(msg) => {
-                warn!("Loading image {} failed: {}", path, msg);        
-            }
+            Err(msg) => Err(format!("Loading image {} failed: {}", path, msg)),
+        }
+    }
+
+    fn load_project(&mut self, path: &str) {
+        let project = match Project::load(path) {
+            Ok(project) => project,
+            Err(message) => return self.show_error(format!(
+                "Could not open project:\n{}\n{}", path, message
+            )),
+        };
+
+        if let Err(message) = self.load_image(&project.source) {
+            return self.show_error(format!("Could not open project:\n{}\n{}", path, message));
+        }
+
+        // Project may come from a different version of the image
+        self.start = Vec2d::new(project.start.0, project.start.1);
+        self.end = Vec2d::new(project.end.0, project.end.1);
+        let (start, end) = rect_to_selection(self.clamp_to_image(self.selection_rect()));
+        self.start = start;
+        self.end = end;
+        self.options = project.mosaic;
+        self.output = project.output;
+        self.last_source_size = None;
+        send_glib(&self.gui, GuiMessage::ShowOptions(self.options));
+        self.render_all();
+    }
+
+    fn save_project(&mut self, path: &str) {
+        let source = match &self.image_path {
+            Some(source) => source.clone(),
+            None => return self.show_error(format!("No image loaded, nothing to save")),
+        };
+
+        let project = Project {
+            source,
+            start: (self.start.x, self.start.y),
+            end: (self.end.x, self.end.y),
+            mosaic: self.options,
+            output: self.output,
         };
+
+        if let Err(message) = project.save(path) {
+            self.show_error(format!("Could not save project into:\n{}\n{}", path, message));
+        }
+    }
+
+    fn show_error(&self, message: String) {
+        warn!("{}", &message);
+        send_glib(&self.gui, GuiMessage::ShowError(message));
     }
 
     fn render_select_image(&mut self) {
@@ -130,7 +471,24 @@ impl LogicState {
                 return;
             }
         }
-        let img = resize(&self.image, self.select_size);
+        let image_size = self.image.size();
+        let range = self.source_view.visible_range(image_size, self.select_size);
+        let scale = self.source_view.scale(image_size, self.select_size);
+        let size = ImgSize::new(
+            max(1, (range.width() as f64*scale) as usize),
+            max(1, (range.height() as f64*scale) as usize),
+        );
+
+        // Magnified pixels are displayed sharp to allow precise selection
+        let filter = if scale > 1.0 { Filter::Nearest } else { self.options.filter };
+        let whole = range.width() as usize == image_size.x 
+            && range.height() as usize == image_size.y;
+
+        let img = match whole {
+            true => resample(&self.image, size, filter),
+            false => resample(&select_patch(&self.image, range), size, filter),
+        };
+
         self.last_source_size = Some(self.select_size);
         self.last_rendered_lines = Some(lines);
         send_glib(&self.gui, GuiMessage::RenderSource(img));
@@ -138,13 +496,14 @@ impl LogicState {
     }
 
     fn selection_lines(&self) -> SelectionLines {
-        let factor = resize_factor(self.image.size(), self.select_size);
+        let (x1, y1) = self.screen_point(self.start.x, self.start.y);
+        let (x2, y2) = self.screen_point(self.end.x, self.end.y);
 
         SelectionLines {
-            x1: (self.start.x as f64*factor) as isize,
-            x2: (self.end.x as f64*factor) as isize,
-            y1: (self.start.y as f64*factor) as isize,
-            y2: (self.end.y as f64*factor) as isize
+            x1: x1.floor() as isize,
+            x2: x2.floor() as isize,
+            y1: y1.floor() as isize,
+            y2: y2.floor() as isize
         }        
     }
 
@@ -156,65 +515,86 @@ impl LogicState {
         Range2d::new(x1..x2, y1..y2)
     }
 
-    fn save_image(&mut self, path: String) {
-        debug!("Save image path: {}", &path);
+    fn selected_size(&self) -> ImgSize {
+        let range = self.selected_range();
+        ImgSize::new(max(1, range.width()) as usize, max(1, range.height()) as usize)
+    }
+
+    fn save_image(&mut self, path: String, output: OutputOptions, cancel: SaveCancel) {
+        debug!("Save image path: {}, output: {:?}", &path, &output);
 
         let buffer = self.get_selected_patch();
+        self.output = output;
+        self.send_patch_size();
 
         send(
             &self.compositor, 
-            CompositeMessage::SaveMosaic((buffer, path))
+            CompositeMessage::SaveMosaic((buffer, path, self.options, output, cancel))
         );
     }    
 
-    fn render_result_image(&self) {
+    fn send_patch_size(&self) {
+        let patch = (self.selected_size(), self.options, self.output);
+        send_glib(&self.gui, GuiMessage::PatchSize(patch));
+    }
+
+    /// Size of the whole mosaic in full resolution
+    fn mosaic_size(&self) -> ImgSize {
+        self.options.output_size(self.selected_size())
+    }
+
+    fn compositor_finished(&mut self, generation: u64) {
+        debug!("Compositor finished generation {}", generation);
+        self.compositor_free = true;
+        // Render the latest request that came while the compositor was busy
+        self.render_result_image();
+    }
+
+    /// Sends preview request to the compositor if it is free, otherwise
+    /// cancels the render in progress, the latest request is sent when 
+    /// the compositor finishes
+    fn render_result_image(&mut self) {
         if !self.result_modified { return; }
+
+        let mosaic = self.mosaic_size();
+        self.send_patch_size();
+
+        self.generation += 1;
+        self.latest_generation.store(self.generation, Ordering::SeqCst);
+
         if !self.compositor_free { return; }
 
+        self.compositor_free = false;
+        self.result_modified = false;
+
+        // Mosaic size changes with selection and pattern
+        self.result_view.clamp(mosaic, self.result_size);
+        let range = self.result_view.visible_range(mosaic, self.result_size);
+        let scale = self.result_view.scale(mosaic, self.result_size);
+        let (left, top) = self.result_view.left_top();
+
+        let view = MosaicView {
+            origin: (left as f64, top as f64),
+            scale,
+            size: ImgSize::new(
+                max(1, (range.width() as f64*scale) as usize),
+                max(1, (range.height() as f64*scale) as usize),
+            ),
+        };
+
         let buffer = self.get_selected_patch();
 
         send(
             &self.compositor, 
-            CompositeMessage::CompositeMosaic((buffer, self.result_size))
+            CompositeMessage::CompositeMosaic((self.generation, buffer, view, self.options))
         );
     }
 
     fn get_selected_patch(&self) -> ImgBuf<Rgba> {
-        let range = self.selected_range();
-
-        if range.width() > 0 && range.height() > 0 {
-            let patch_size = ImgSize::new(range.width() as usize, range.height() as usize);
-            let mut buffer = ImgBuf::<Rgba>::new(patch_size);
-            let output_range = buffer.range();
-            map_range(&self.image, &mut buffer, range, output_range, |i, _| i);    
-            buffer
-        } else {
-            ImgBuf::<Rgba>::new_init(ImgSize::new(1, 1), [0, 0, 0, 0])
-        }        
+        select_patch(&self.image, self.selected_range())
     }
 }
 
-fn load_image(path: &str) -> Result<ImgBuf<Rgba>, String> {
-    let buf = convert_err(open(path))?.into_rgba();
-    let size = ImgSize::new(buf.width() as usize, buf.height() as usize);
-    Ok(ImgBuf::from_vec(size, bytes_to_rgba(buf.into_vec())))
+fn rect_to_selection(rect: SelectionRect) -> Selection {
+    (Vec2d::new(rect.x, rect.y), Vec2d::new(rect.x + rect.width, rect.y + rect.height))
 }
-
-fn bytes_to_rgba(input: Vec<u8>) -> Vec<Rgba> {
-    let pixels = input.len()/4;
-    let mut result = vec![[0, 0, 0, 0]; pixels];
-    let mut offset = 0;
-
-    while offset < input.len() {
-        result[offset/4] = [
-            input[offset], 
-            input[offset + 1],
-            input[offset + 2],
-            input[offset + 3],
-        ];
-        offset += 4;
-    }
-
-    result
-}
-
@@ -0,0 +1,91 @@
+//! Zoom and pan of an image displayed in a view
+
+use nanocv::{ImgSize, Range2d};
+use std::cmp::min;
+use crate::common::resize_factor;
+
+/// Maximal number of view pixels per image pixel, zoom reaches beyond 1:1
+/// up to this scale regardless of the image size
+const MAX_SCALE: f64 = 16.0;
+
+/// Visible part of an image, zoom 1.0 fits the whole image into the view
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    zoom: f64,
+    /// Image point displayed in the top left corner of the view
+    origin: (f64, f64),
+}
+
+impl View {
+    pub fn new() -> Self {
+        Self {zoom: 1.0, origin: (0.0, 0.0)}
+    }
+
+    /// Number of view pixels per image pixel
+    pub fn scale(&self, image: ImgSize, view: ImgSize) -> f64 {
+        resize_factor(image, view)*self.zoom
+    }
+
+    /// Image pixel displayed in the top left corner of the view
+    pub fn left_top(&self) -> (isize, isize) {
+        (self.origin.0.floor() as isize, self.origin.1.floor() as isize)
+    }
+
+    /// Converts view point into image coordinates
+    pub fn to_image(&self, x: f64, y: f64, image: ImgSize, view: ImgSize) -> (f64, f64) {
+        let scale = self.scale(image, view);
+        let (left, top) = self.left_top();
+        (left as f64 + x/scale, top as f64 + y/scale)
+    }
+
+    /// Converts image point into view coordinates
+    pub fn to_view(&self, x: f64, y: f64, image: ImgSize, view: ImgSize) -> (f64, f64) {
+        let scale = self.scale(image, view);
+        let (left, top) = self.left_top();
+        ((x - left as f64)*scale, (y - top as f64)*scale)
+    }
+
+    /// Range of image pixels visible in the view
+    pub fn visible_range(&self, image: ImgSize, view: ImgSize) -> Range2d<isize> {
+        let scale = self.scale(image, view);
+        let (left, top) = self.left_top();
+        let right = min(image.x as isize, left + (view.x as f64/scale).ceil() as isize);
+        let bottom = min(image.y as isize, top + (view.y as f64/scale).ceil() as isize);
+        Range2d::new(left..right, top..bottom)
+    }
+
+    /// Changes zoom by the factor keeping image point under the view point in place
+    pub fn zoom_at(&mut self, factor: f64, x: f64, y: f64, image: ImgSize, view: ImgSize) {
+        let scale = self.scale(image, view);
+        let (px, py) = (self.origin.0 + x/scale, self.origin.1 + y/scale);
+        self.zoom = self.zoom*factor;
+        self.limit_zoom(image, view);
+        let scale = self.scale(image, view);
+        self.origin = (px - x/scale, py - y/scale);
+        self.clamp(image, view);
+    }
+
+    /// Moves the image by the given number of view pixels
+    pub fn pan(&mut self, dx: f64, dy: f64, image: ImgSize, view: ImgSize) {
+        let scale = self.scale(image, view);
+        self.origin = (self.origin.0 - dx/scale, self.origin.1 - dy/scale);
+        self.clamp(image, view);
+    }
+
+    /// Keeps the view within the image
+    pub fn clamp(&mut self, image: ImgSize, view: ImgSize) {
+        self.limit_zoom(image, view);
+        let scale = self.scale(image, view);
+        let max_x = (image.x as f64 - view.x as f64/scale).max(0.0);
+        let max_y = (image.y as f64 - view.y as f64/scale).max(0.0);
+        self.origin.0 = self.origin.0.max(0.0).min(max_x);
+        self.origin.1 = self.origin.1.max(0.0).min(max_y);
+    }
+
+    /// Keeps zoom between fitting the whole image and the maximal scale,
+    /// images already fitted above the maximal scale are not zoomed
+    fn limit_zoom(&mut self, image: ImgSize, view: ImgSize) {
+        let max_zoom = (MAX_SCALE/resize_factor(image, view)).max(1.0);
+        self.zoom = self.zoom.max(1.0).min(max_zoom);
+    }
+}
@@ -2,10 +2,11 @@
 
 use log::*;
 use glib::{Sender as GlibSender};
-use std::sync::mpsc::SyncSender;
+use std::sync::{Arc, atomic::{AtomicU64, AtomicBool}, mpsc::SyncSender};
 use nanocv::{ImgSize, ImgBuf};
 use crate::common::log_err;
-pub type Rgba = [u8; 4];
+use nanomosaic::{PatternId, MosaicOptions, OutputOptions, Filter, SeamBlend, SaveStage};
+pub use nanomosaic::Rgba;
 
 pub type LogicSender = SyncSender<Option<LogicMessage>>;
 pub type CompositorSender = SyncSender<Option<CompositeMessage>>;
@@ -14,18 +15,39 @@ pub type CompositorSender = SyncSender<Option<CompositeMessage>>;
 pub enum LogicMessage {
     InitGui(GlibSender<GuiMessage>),
     LoadImage(String),
-    SaveImage(String),
+    LoadProject(String),
+    SaveProject(String),
+    SaveImage((String, OutputOptions, SaveCancel)),
     ImageResized((ImageId, ImgSize)),
     MouseDown((u32, f64, f64)),
-    CompositorFinished,
-    ReturnBuffer(ImgBuf<Rgba>),
+    MouseMove((f64, f64)),
+    MouseUp(u32),
+    /// Zoom factor at the view point
+    Zoom((ImageId, f64, f64, f64)),
+    /// Move of the view content in view pixels
+    Pan((ImageId, f64, f64)),
+    SetSelection(SelectionRect),
+    SetConstraint(SelectionConstraint),
+    MoveSelection((isize, isize)),
+    ResizeSelection((isize, isize)),
+    RefineSelection,
+    Undo,
+    Redo,
+    SetPattern(PatternId),
+    SetSegments(u32),
+    SetRepeat((usize, usize)),
+    SetFilter(Filter),
+    SetBlend((SeamBlend, usize)),
+    /// Compositor finished or cancelled preview of given generation
+    CompositorFinished(u64),
 }
 
 #[derive(Clone)]
 pub enum CompositeMessage {
     InitGui(GlibSender<GuiMessage>),
-    CompositeMosaic((ImgBuf<Rgba>, ImgSize)),
-    SaveMosaic((ImgBuf<Rgba>, String)),
+    /// Preview request with its generation number
+    CompositeMosaic((u64, ImgBuf<Rgba>, MosaicView, MosaicOptions)),
+    SaveMosaic((ImgBuf<Rgba>, String, MosaicOptions, OutputOptions, SaveCancel)),
 }
 
 #[derive(Clone)]
@@ -33,7 +55,16 @@ pub enum GuiMessage {
     RenderSource(ImgBuf<Rgba>),
     RenderTarget(ImgBuf<Rgba>),
     RenderLines(SelectionLines),
+    /// Size of the selected patch, options used to create saved image
+    /// and output options offered in the save dialog
+    PatchSize((ImgSize, MosaicOptions, OutputOptions)),
+    ShowOptions(MosaicOptions),
+    ShowSelection(SelectionRect),
+    SetCursor(Handle),
     ShowError(String),    
+    /// Finished fraction of the running save stage
+    SaveProgress((SaveStage, f64)),
+    SaveFinished(SaveOutcome),
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -57,6 +88,101 @@ pub fn send_glib<T>(sender: &Option<GlibSender<T>>, message: T) {
     }
 }
 
+/// Selection in source image pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SelectionRect {
+    pub x: isize,
+    pub y: isize,
+    pub width: isize,
+    pub height: isize,
+}
+
+/// Limits selection shape while it is edited
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionConstraint {
+    Free,
+    /// Width to height ratio
+    AspectRatio((u32, u32)),
+    /// Exact width and height in source image pixels
+    FixedSize((isize, isize)),
+    /// Width and height are rounded to the nearest power of two
+    PowerOfTwo,
+}
+
+/// Part of the selection grabbed by mouse
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Handle {
+    /// Outside of the selection, dragging creates new selection
+    New,
+    /// Inside of the selection, dragging moves the whole selection
+    Move,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Handle {
+    pub fn moves_left(self) -> bool {
+        match self {
+            Handle::Left | Handle::TopLeft | Handle::BottomLeft => true,
+            _ => false,
+        }
+    }
+
+    pub fn moves_right(self) -> bool {
+        match self {
+            Handle::Right | Handle::TopRight | Handle::BottomRight => true,
+            _ => false,
+        }
+    }
+
+    pub fn moves_top(self) -> bool {
+        match self {
+            Handle::Top | Handle::TopLeft | Handle::TopRight => true,
+            _ => false,
+        }
+    }
+
+    pub fn moves_bottom(self) -> bool {
+        match self {
+            Handle::Bottom | Handle::BottomLeft | Handle::BottomRight => true,
+            _ => false,
+        }
+    }
+}
+
+/// Generation of the latest requested preview shared by logic and compositor
+/// threads, compositor cancels renders of older generations
+pub type LatestGeneration = Arc<AtomicU64>;
+
+/// Flag set by the GUI to cancel running save
+pub type SaveCancel = Arc<AtomicBool>;
+
+#[derive(Clone, Debug)]
+pub enum SaveOutcome {
+    /// Image saved into given path
+    Saved(String),
+    Cancelled,
+    /// Error has been already reported by `ShowError`
+    Failed,
+}
+
+/// Visible part of the mosaic in the result view
+#[derive(Clone, Copy, Debug)]
+pub struct MosaicView {
+    /// Mosaic point in full resolution displayed in the top left corner
+    pub origin: (f64, f64),
+    /// Number of view pixels per mosaic pixel
+    pub scale: f64,
+    /// Size of the rendered image
+    pub size: ImgSize,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct SelectionLines {
     pub x1: isize,